@@ -1,18 +1,154 @@
-use crate::db::{ConnectionManager, ConfigAnalyzer, ConfigIssue, PerformanceIssue};
+use crate::db::{
+    ConnectionManager, ConfigAnalyzer, ConfigIssue, PerformanceIssue, WraparoundAnalyzer,
+    WraparoundReport, XidRateTracker, IndexAdvisor, IndexReport, RuleRegistry, RuleInfo, AnalyzerConfigStatus,
+    IssueHistory, IssueRecord, IssueStatus, TrackedIssue, unix_now, HealthScorer, ServerHealth,
+};
 use tauri::State;
 
 #[tauri::command]
 pub async fn analyze_configuration(
     server_id: String,
     manager: State<'_, ConnectionManager>,
+    rules: State<'_, RuleRegistry>,
 ) -> Result<Vec<ConfigIssue>, String> {
-    ConfigAnalyzer::analyze_configuration(&manager, &server_id).await
+    ConfigAnalyzer::analyze_configuration(&manager, &server_id, &rules).await
 }
 
 #[tauri::command]
 pub async fn detect_performance_issues(
     server_id: String,
     manager: State<'_, ConnectionManager>,
+    rules: State<'_, RuleRegistry>,
+    xid_tracker: State<'_, XidRateTracker>,
 ) -> Result<Vec<PerformanceIssue>, String> {
-    ConfigAnalyzer::detect_performance_issues(&manager, &server_id).await
+    ConfigAnalyzer::detect_performance_issues(&manager, &server_id, &rules, &xid_tracker).await
+}
+
+#[tauri::command]
+pub async fn get_wraparound_report(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+    xid_tracker: State<'_, XidRateTracker>,
+) -> Result<WraparoundReport, String> {
+    WraparoundAnalyzer::get_report(&manager, &server_id, &xid_tracker).await
+}
+
+#[tauri::command]
+pub async fn get_index_report(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<IndexReport, String> {
+    IndexAdvisor::get_report(&manager, &server_id).await
+}
+
+#[tauri::command]
+pub fn list_rules(rules: State<'_, RuleRegistry>) -> Vec<RuleInfo> {
+    rules.list()
+}
+
+#[tauri::command]
+pub fn set_rule_enabled(
+    rule_id: String,
+    enabled: bool,
+    rules: State<'_, RuleRegistry>,
+) -> Result<(), String> {
+    rules.set_enabled(&rule_id, enabled)
+}
+
+#[tauri::command]
+pub fn get_analyzer_config_status(rules: State<'_, RuleRegistry>) -> AnalyzerConfigStatus {
+    rules.config_status()
+}
+
+#[tauri::command]
+pub fn reload_analyzer_config(rules: State<'_, RuleRegistry>) -> AnalyzerConfigStatus {
+    rules.reload()
+}
+
+/// Analyze the configuration and record the issues; with `only_new`, known
+/// and acknowledged issues are left out
+#[tauri::command]
+pub async fn track_configuration_issues(
+    server_id: String,
+    only_new: bool,
+    manager: State<'_, ConnectionManager>,
+    rules: State<'_, RuleRegistry>,
+    history: State<'_, IssueHistory>,
+) -> Result<Vec<TrackedIssue<ConfigIssue>>, String> {
+    let run = ConfigAnalyzer::run_configuration_rules(&manager, &server_id, &rules).await?;
+    let mut tracked = history.record::<ConfigIssue>(&server_id, run, unix_now())?;
+    if only_new {
+        tracked.retain(|t| t.status != IssueStatus::Known);
+    }
+    Ok(tracked)
+}
+
+/// Detect performance issues and record them; with `only_new`, known and
+/// acknowledged issues are left out
+#[tauri::command]
+pub async fn track_performance_issues(
+    server_id: String,
+    only_new: bool,
+    manager: State<'_, ConnectionManager>,
+    rules: State<'_, RuleRegistry>,
+    xid_tracker: State<'_, XidRateTracker>,
+    history: State<'_, IssueHistory>,
+) -> Result<Vec<TrackedIssue<PerformanceIssue>>, String> {
+    let run = ConfigAnalyzer::run_performance_rules(&manager, &server_id, &rules, &xid_tracker)
+        .await?;
+    let mut tracked = history.record::<PerformanceIssue>(&server_id, run, unix_now())?;
+    if only_new {
+        tracked.retain(|t| t.status != IssueStatus::Known);
+    }
+    Ok(tracked)
+}
+
+#[tauri::command]
+pub fn get_issue_history(
+    server_id: String,
+    include_resolved: bool,
+    history: State<'_, IssueHistory>,
+) -> Result<Vec<IssueRecord>, String> {
+    history.list(&server_id, include_resolved)
+}
+
+/// Accept an issue, or snooze it until `expires_at` (unix seconds)
+#[tauri::command]
+pub fn acknowledge_issue(
+    server_id: String,
+    fingerprint: String,
+    comment: String,
+    expires_at: Option<i64>,
+    history: State<'_, IssueHistory>,
+) -> Result<(), String> {
+    history.acknowledge(&server_id, &fingerprint, &comment, expires_at, unix_now())
+}
+
+#[tauri::command]
+pub fn clear_issue_acknowledgement(
+    server_id: String,
+    fingerprint: String,
+    history: State<'_, IssueHistory>,
+) -> Result<(), String> {
+    history.clear_acknowledgement(&server_id, &fingerprint)
+}
+
+#[tauri::command]
+pub async fn get_server_health(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+    rules: State<'_, RuleRegistry>,
+    xid_tracker: State<'_, XidRateTracker>,
+) -> Result<ServerHealth, String> {
+    HealthScorer::score(&manager, &server_id, &rules, &xid_tracker).await
+}
+
+/// Health of all connected servers, worst first
+#[tauri::command]
+pub async fn get_fleet_health(
+    manager: State<'_, ConnectionManager>,
+    rules: State<'_, RuleRegistry>,
+    xid_tracker: State<'_, XidRateTracker>,
+) -> Result<Vec<ServerHealth>, String> {
+    Ok(HealthScorer::score_all(&manager, &rules, &xid_tracker).await)
 }
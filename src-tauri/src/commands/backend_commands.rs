@@ -0,0 +1,40 @@
+use crate::db::{
+    AuditEntry, AuditLog, BackendAction, BackendActionResult, BackendController, BackendTarget,
+    ConnectionManager,
+};
+use tauri::State;
+
+#[tauri::command]
+pub async fn cancel_backend(
+    server_id: String,
+    target: BackendTarget,
+    manager: State<'_, ConnectionManager>,
+) -> Result<BackendActionResult, String> {
+    BackendController::signal(&manager, &server_id, &target, BackendAction::Cancel).await
+}
+
+#[tauri::command]
+pub async fn terminate_backend(
+    server_id: String,
+    target: BackendTarget,
+    manager: State<'_, ConnectionManager>,
+) -> Result<BackendActionResult, String> {
+    BackendController::signal(&manager, &server_id, &target, BackendAction::Terminate).await
+}
+
+#[tauri::command]
+pub async fn terminate_idle_in_transaction(
+    server_id: String,
+    min_idle_minutes: i32,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<BackendActionResult>, String> {
+    BackendController::terminate_idle_in_transaction(&manager, &server_id, min_idle_minutes).await
+}
+
+#[tauri::command]
+pub fn get_audit_log(
+    server_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<AuditEntry>, String> {
+    AuditLog::list(server_id.as_deref(), limit.unwrap_or(200))
+}
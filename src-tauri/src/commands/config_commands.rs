@@ -1,4 +1,7 @@
-use crate::db::{ConnectionManager, ConfigCollector, PostgresConfig, HardwareInfo};
+use crate::db::{
+    ConnectionManager, ConfigCollector, PostgresConfig, HardwareInfo, HardwareProfile, ConfigTuner,
+    TuningRequest, TuningRecommendation, ServerSettings,
+};
 use tauri::State;
 
 #[tauri::command]
@@ -9,6 +12,14 @@ pub async fn get_all_settings(
     ConfigCollector::get_all_settings(&manager, &server_id).await
 }
 
+#[tauri::command]
+pub async fn get_typed_settings(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<ServerSettings, String> {
+    ConfigCollector::get_typed_settings(&manager, &server_id).await
+}
+
 #[tauri::command]
 pub async fn get_hardware_info(
     server_id: String,
@@ -16,3 +27,25 @@ pub async fn get_hardware_info(
 ) -> Result<HardwareInfo, String> {
     ConfigCollector::get_hardware_info(&manager, &server_id).await
 }
+
+#[tauri::command]
+pub fn get_hardware_profile(server_id: String) -> Option<HardwareProfile> {
+    ConfigCollector::get_hardware_profile(&server_id)
+}
+
+#[tauri::command]
+pub fn set_hardware_profile(
+    server_id: String,
+    profile: Option<HardwareProfile>,
+) -> Result<(), String> {
+    ConfigCollector::set_hardware_profile(&server_id, profile)
+}
+
+#[tauri::command]
+pub async fn generate_tuned_config(
+    server_id: String,
+    request: TuningRequest,
+    manager: State<'_, ConnectionManager>,
+) -> Result<TuningRecommendation, String> {
+    ConfigTuner::generate(&manager, &server_id, &request).await
+}
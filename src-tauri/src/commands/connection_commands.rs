@@ -1,4 +1,7 @@
-use crate::db::{ConnectionConfig, ConnectionManager, ConnectionTestResult, ServerInfo};
+use crate::db::{
+    ConnectionConfig, ConnectionManager, ConnectionTestResult, ImportPreview, ImportRequest,
+    ImportResult, ServerData, ServerImporter, ServerInfo, ClusterTopology, TopologyDiscovery,
+};
 use tauri::State;
 
 #[tauri::command]
@@ -21,8 +24,10 @@ pub async fn add_server(
 pub async fn remove_server(
     id: String,
     manager: State<'_, ConnectionManager>,
+    server_data: State<'_, ServerData>,
 ) -> Result<(), String> {
-    manager.remove_connection(&id).await
+    manager.remove_connection(&id).await?;
+    server_data.forget_server(&id)
 }
 
 #[tauri::command]
@@ -47,3 +52,27 @@ pub async fn reconnect_server(
 ) -> Result<(), String> {
     manager.reconnect(&id).await
 }
+
+#[tauri::command]
+pub fn preview_server_import(
+    request: ImportRequest,
+    manager: State<'_, ConnectionManager>,
+) -> Result<ImportPreview, String> {
+    Ok(ServerImporter::preview(&manager, &request))
+}
+
+#[tauri::command]
+pub fn import_servers(
+    request: ImportRequest,
+    selected: Option<Vec<usize>>,
+    manager: State<'_, ConnectionManager>,
+) -> Result<ImportResult, String> {
+    Ok(ServerImporter::import(&manager, &request, selected.as_deref()))
+}
+
+#[tauri::command]
+pub async fn discover_topology(
+    manager: State<'_, ConnectionManager>,
+) -> Result<ClusterTopology, String> {
+    TopologyDiscovery::discover(&manager).await
+}
@@ -0,0 +1,25 @@
+use crate::db::{HistoryQuery, MetricSeries, MetricsSampler, SamplerConfig};
+use tauri::State;
+
+#[tauri::command]
+pub fn get_metric_history(
+    query: HistoryQuery,
+    sampler: State<'_, MetricsSampler>,
+) -> Result<Vec<MetricSeries>, String> {
+    sampler.store().query(&query)
+}
+
+#[tauri::command]
+pub fn get_sampler_config(
+    sampler: State<'_, MetricsSampler>,
+) -> Result<SamplerConfig, String> {
+    Ok(sampler.config())
+}
+
+#[tauri::command]
+pub fn update_sampler_config(
+    config: SamplerConfig,
+    sampler: State<'_, MetricsSampler>,
+) -> Result<(), String> {
+    sampler.set_config(config)
+}
@@ -1,6 +1,10 @@
 use crate::db::{
-    ConnectionManager, MetricsCollector, DatabaseStats, QueryStat,
+    ConnectionManager, MetricsCollector, DatabaseStats, QueryStat, QuerySortKey,
     ActiveQuery, TableStats, IndexStats, LockInfo, BgWriterStats, DatabaseSize, ExplainPlan,
+    RateEngine, ServerRates, StatementSnapshots, SnapshotInfo, StatementsDiff,
+    LockCollector, LockTree, ReplicationStat, ReplicationSlot, WalReceiverStat, RecoveryStatus,
+    OperationProgress, ProgressTracker, BloatCollector, RelationBloat, SizeCollector,
+    RelationSizeRequest, RelationSizeInventory, IndexSuggester, IndexSuggestions,
 };
 use tauri::State;
 
@@ -16,9 +20,10 @@ pub async fn get_database_stats(
 pub async fn get_top_queries(
     server_id: String,
     limit: i32,
+    sort_by: Option<QuerySortKey>,
     manager: State<'_, ConnectionManager>,
 ) -> Result<Vec<QueryStat>, String> {
-    MetricsCollector::get_top_queries(&manager, &server_id, limit).await
+    MetricsCollector::get_top_queries(&manager, &server_id, limit, sort_by.unwrap_or_default()).await
 }
 
 #[tauri::command]
@@ -47,6 +52,26 @@ pub async fn get_index_stats(
     MetricsCollector::get_index_stats(&manager, &server_id, limit).await
 }
 
+#[tauri::command]
+pub async fn get_table_bloat(
+    server_id: String,
+    limit: i32,
+    exact: bool,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<RelationBloat>, String> {
+    BloatCollector::get_table_bloat(&manager, &server_id, limit, exact).await
+}
+
+#[tauri::command]
+pub async fn get_index_bloat(
+    server_id: String,
+    limit: i32,
+    exact: bool,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<RelationBloat>, String> {
+    BloatCollector::get_index_bloat(&manager, &server_id, limit, exact).await
+}
+
 #[tauri::command]
 pub async fn get_locks(
     server_id: String,
@@ -55,6 +80,14 @@ pub async fn get_locks(
     MetricsCollector::get_locks(&manager, &server_id).await
 }
 
+#[tauri::command]
+pub async fn get_lock_tree(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<LockTree, String> {
+    LockCollector::get_lock_tree(&manager, &server_id).await
+}
+
 #[tauri::command]
 pub async fn get_bgwriter_stats(
     server_id: String,
@@ -63,6 +96,47 @@ pub async fn get_bgwriter_stats(
     MetricsCollector::get_bgwriter_stats(&manager, &server_id).await
 }
 
+#[tauri::command]
+pub async fn get_replication_stats(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<ReplicationStat>, String> {
+    MetricsCollector::get_replication_stats(&manager, &server_id).await
+}
+
+#[tauri::command]
+pub async fn get_replication_slots(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Vec<ReplicationSlot>, String> {
+    MetricsCollector::get_replication_slots(&manager, &server_id).await
+}
+
+#[tauri::command]
+pub async fn get_wal_receiver(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<Option<WalReceiverStat>, String> {
+    MetricsCollector::get_wal_receiver(&manager, &server_id).await
+}
+
+#[tauri::command]
+pub async fn get_recovery_status(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<RecoveryStatus, String> {
+    MetricsCollector::get_recovery_status(&manager, &server_id).await
+}
+
+#[tauri::command]
+pub async fn get_maintenance_progress(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+    progress: State<'_, ProgressTracker>,
+) -> Result<Vec<OperationProgress>, String> {
+    progress.get_progress(&manager, &server_id).await
+}
+
 #[tauri::command]
 pub async fn get_database_sizes(
     server_id: String,
@@ -71,6 +145,15 @@ pub async fn get_database_sizes(
     MetricsCollector::get_database_sizes(&manager, &server_id).await
 }
 
+#[tauri::command]
+pub async fn get_relation_sizes(
+    server_id: String,
+    request: RelationSizeRequest,
+    manager: State<'_, ConnectionManager>,
+) -> Result<RelationSizeInventory, String> {
+    SizeCollector::get_relation_sizes(&manager, &server_id, &request).await
+}
+
 #[tauri::command]
 pub async fn explain_query(
     server_id: String,
@@ -80,3 +163,63 @@ pub async fn explain_query(
 ) -> Result<ExplainPlan, String> {
     MetricsCollector::explain_query(&manager, &server_id, &query, analyze).await
 }
+
+#[tauri::command]
+pub async fn suggest_indexes(
+    server_id: String,
+    query: String,
+    manager: State<'_, ConnectionManager>,
+) -> Result<IndexSuggestions, String> {
+    IndexSuggester::suggest(&manager, &server_id, &query).await
+}
+
+#[tauri::command]
+pub async fn get_server_rates(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+    rates: State<'_, RateEngine>,
+) -> Result<ServerRates, String> {
+    rates.get_rates(&manager, &server_id).await
+}
+
+#[tauri::command]
+pub async fn take_statements_snapshot(
+    server_id: String,
+    manager: State<'_, ConnectionManager>,
+    snapshots: State<'_, StatementSnapshots>,
+) -> Result<SnapshotInfo, String> {
+    snapshots.take_snapshot(&manager, &server_id).await
+}
+
+#[tauri::command]
+pub fn list_statements_snapshots(
+    server_id: String,
+    snapshots: State<'_, StatementSnapshots>,
+) -> Result<Vec<SnapshotInfo>, String> {
+    Ok(snapshots.list_snapshots(&server_id))
+}
+
+/// Diff two snapshots; without `to_snapshot_id` a new snapshot is taken now
+#[tauri::command]
+pub async fn diff_statements_snapshots(
+    server_id: String,
+    from_snapshot_id: String,
+    to_snapshot_id: Option<String>,
+    sort_by: Option<QuerySortKey>,
+    limit: i32,
+    manager: State<'_, ConnectionManager>,
+    snapshots: State<'_, StatementSnapshots>,
+) -> Result<StatementsDiff, String> {
+    let to_snapshot_id = match to_snapshot_id {
+        Some(id) => id,
+        None => snapshots.take_snapshot(&manager, &server_id).await?.id,
+    };
+
+    snapshots.diff(
+        &server_id,
+        &from_snapshot_id,
+        &to_snapshot_id,
+        sort_by.unwrap_or_default(),
+        limit.max(0) as usize,
+    )
+}
@@ -2,8 +2,12 @@ mod connection_commands;
 mod metrics_commands;
 mod config_commands;
 mod analyzer_commands;
+mod history_commands;
+mod backend_commands;
 
 pub use connection_commands::*;
 pub use metrics_commands::*;
 pub use config_commands::*;
 pub use analyzer_commands::*;
+pub use history_commands::*;
+pub use backend_commands::*;
@@ -0,0 +1,561 @@
+use super::analyzer::{stable_hash, IssueSeverity, PerformanceIssue};
+use super::connection::ConnectionManager;
+use super::rules::{IssueKind, Rule, RuleCategory, RuleContext, RuleInput, RuleIssue};
+use super::storage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// Rows kept per declarative rule
+const CUSTOM_ROW_LIMIT: i64 = 100;
+const CUSTOM_STATEMENT_TIMEOUT: &str = "10s";
+
+/// Limits used by the built-in rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// Buffer cache hit ratio (%) below which the ratio is reported
+    pub cache_hit_ratio_min: f64,
+    /// Sequential scans and rows read before a table is reported
+    pub seq_scan_min: i64,
+    pub seq_tup_read_min: i64,
+    /// max_connections is reported above twice min(cores * per_core, cap)
+    pub max_connections_per_core: i32,
+    pub max_connections_cap: i32,
+    pub work_mem_min_mb: i64,
+    /// Share of RAM recommended for shared_buffers, capped at shared_buffers_max_mb
+    pub shared_buffers_ram_ratio: f64,
+    pub shared_buffers_max_mb: i64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            cache_hit_ratio_min: 90.0,
+            seq_scan_min: 1000,
+            seq_tup_read_min: 100_000,
+            max_connections_per_core: 50,
+            max_connections_cap: 200,
+            work_mem_min_mb: 4,
+            shared_buffers_ram_ratio: 0.25,
+            shared_buffers_max_mb: 8192,
+        }
+    }
+}
+
+/// Thresholds set in one section of the config file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThresholdOverrides {
+    pub cache_hit_ratio_min: Option<f64>,
+    pub seq_scan_min: Option<i64>,
+    pub seq_tup_read_min: Option<i64>,
+    pub max_connections_per_core: Option<i32>,
+    pub max_connections_cap: Option<i32>,
+    pub work_mem_min_mb: Option<i64>,
+    pub shared_buffers_ram_ratio: Option<f64>,
+    pub shared_buffers_max_mb: Option<i64>,
+}
+
+impl ThresholdOverrides {
+    fn apply(&self, thresholds: &mut Thresholds) {
+        macro_rules! set {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    thresholds.$field = value;
+                })*
+            };
+        }
+        set!(
+            cache_hit_ratio_min,
+            seq_scan_min,
+            seq_tup_read_min,
+            max_connections_per_core,
+            max_connections_cap,
+            work_mem_min_mb,
+            shared_buffers_ram_ratio,
+            shared_buffers_max_mb
+        );
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerGroup {
+    /// Server ids or names
+    pub servers: Vec<String>,
+    #[serde(default)]
+    pub thresholds: ThresholdOverrides,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerOverrides {
+    #[serde(default)]
+    pub thresholds: ThresholdOverrides,
+}
+
+/// A rule defined in the config file: rows of `query` matching `condition`
+/// are reported with `message`, where `{column}` is replaced by the row's
+/// value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeclarativeRuleSpec {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "default_severity")]
+    pub severity: IssueSeverity,
+    /// Read-only SELECT or WITH query
+    pub query: String,
+    /// e.g. `idle_secs > 300 and state = 'idle in transaction'`; every row
+    /// is reported when omitted
+    #[serde(default)]
+    pub condition: Option<String>,
+    pub message: String,
+    #[serde(default)]
+    pub recommendation: Option<String>,
+    /// Template naming what a row is about, e.g. `{schemaname}.{relname}`,
+    /// so each row is tracked as its own issue. Without it rows are told
+    /// apart by a hash of all their values, so a row whose values change
+    /// is tracked as a new issue.
+    #[serde(default)]
+    pub object: Option<String>,
+    /// Limit the rule to these server ids or names, or groups
+    #[serde(default)]
+    pub servers: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+fn default_severity() -> IssueSeverity {
+    IssueSeverity::Warning
+}
+
+/// Contents of analyzer.toml in the data directory. Thresholds apply in
+/// the order defaults, top-level, groups (by name), server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyzerConfigFile {
+    #[serde(default)]
+    pub thresholds: ThresholdOverrides,
+    #[serde(default)]
+    pub groups: BTreeMap<String, ServerGroup>,
+    #[serde(default)]
+    pub servers: BTreeMap<String, ServerOverrides>,
+    #[serde(default)]
+    pub rules: Vec<DeclarativeRuleSpec>,
+}
+
+/// Result of loading the config file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalyzerConfigStatus {
+    pub path: Option<String>,
+    pub exists: bool,
+    pub rule_count: usize,
+    /// Parse and validation errors; invalid rules are skipped, a file
+    /// that does not parse is ignored entirely
+    pub errors: Vec<String>,
+}
+
+impl AnalyzerConfigFile {
+    pub fn path() -> Option<PathBuf> {
+        storage::data_dir().map(|p| p.join("analyzer.toml"))
+    }
+
+    /// Load and validate the file, keeping only valid rules
+    pub fn load(builtin_ids: &[String]) -> (AnalyzerConfigFile, AnalyzerConfigStatus) {
+        let path = Self::path();
+        let mut status = AnalyzerConfigStatus {
+            path: path.as_ref().map(|p| p.display().to_string()),
+            exists: false,
+            rule_count: 0,
+            errors: Vec::new(),
+        };
+
+        let Some(content) = path.and_then(|p| fs::read_to_string(p).ok()) else {
+            return (AnalyzerConfigFile::default(), status);
+        };
+        status.exists = true;
+
+        let mut file: AnalyzerConfigFile = match toml::from_str(&content) {
+            Ok(file) => file,
+            Err(e) => {
+                status
+                    .errors
+                    .push(format!("Failed to parse analyzer.toml: {}", e));
+                return (AnalyzerConfigFile::default(), status);
+            }
+        };
+
+        let mut seen: HashSet<String> = builtin_ids.iter().cloned().collect();
+        let groups: Vec<String> = file.groups.keys().cloned().collect();
+        file.rules.retain(|rule| match rule.validate(&groups) {
+            Err(e) => {
+                status.errors.push(format!("Rule {}: {}", rule.id, e));
+                false
+            }
+            Ok(()) if !seen.insert(rule.id.clone()) => {
+                status
+                    .errors
+                    .push(format!("Rule {}: id is already in use", rule.id));
+                false
+            }
+            Ok(()) => true,
+        });
+        status.rule_count = file.rules.len();
+
+        (file, status)
+    }
+
+    /// Groups containing the server, by id or name, in name order
+    fn groups_of(&self, server_id: &str, server_name: Option<&str>) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|(_, group)| {
+                group
+                    .servers
+                    .iter()
+                    .any(|s| s == server_id || Some(s.as_str()) == server_name)
+            })
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn thresholds_for(&self, server_id: &str, server_name: Option<&str>) -> Thresholds {
+        let mut thresholds = Thresholds::default();
+        self.thresholds.apply(&mut thresholds);
+
+        for group in self.groups_of(server_id, server_name) {
+            self.groups[group].thresholds.apply(&mut thresholds);
+        }
+
+        let server = self
+            .servers
+            .get(server_id)
+            .or_else(|| server_name.and_then(|name| self.servers.get(name)));
+        if let Some(server) = server {
+            server.thresholds.apply(&mut thresholds);
+        }
+
+        thresholds
+    }
+
+    /// Whether a declarative rule is scoped to the server
+    pub fn rule_applies(
+        &self,
+        rule: &DeclarativeRuleSpec,
+        server_id: &str,
+        server_name: Option<&str>,
+    ) -> bool {
+        if rule.servers.is_empty() && rule.groups.is_empty() {
+            return true;
+        }
+        rule.servers
+            .iter()
+            .any(|s| s == server_id || Some(s.as_str()) == server_name)
+            || self
+                .groups_of(server_id, server_name)
+                .iter()
+                .any(|group| rule.groups.iter().any(|g| g == group))
+    }
+}
+
+impl DeclarativeRuleSpec {
+    fn validate(&self, groups: &[String]) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("id must not be empty".to_string());
+        }
+        let query = self.query.trim_start().to_uppercase();
+        if !query.starts_with("SELECT") && !query.starts_with("WITH") {
+            return Err("query must be a SELECT or WITH query".to_string());
+        }
+        if let Some(condition) = &self.condition {
+            Condition::parse(condition)?;
+        }
+        template_fields(&self.message)?;
+        if let Some(object) = &self.object {
+            template_fields(object)?;
+        }
+        if let Some(group) = self.groups.iter().find(|g| !groups.contains(g)) {
+            return Err(format!("unknown group {}", group));
+        }
+        Ok(())
+    }
+
+    /// Run the query read-only and return its rows as JSON objects
+    pub async fn fetch_rows(
+        &self,
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<Vec<Value>, String> {
+        let mut client = manager.get_client(server_id).await?;
+        let transaction = client
+            .build_transaction()
+            .read_only(true)
+            .start()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        transaction
+            .batch_execute(&format!(
+                "SET LOCAL statement_timeout = '{}'",
+                CUSTOM_STATEMENT_TIMEOUT
+            ))
+            .await
+            .map_err(|e| format!("Failed to set statement timeout: {}", e))?;
+
+        let query = format!(
+            "SELECT to_jsonb(q) FROM ({}) q LIMIT {}",
+            self.query.trim().trim_end_matches(';'),
+            CUSTOM_ROW_LIMIT
+        );
+        let rows = transaction
+            .query(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to run rule query: {}", e))?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+}
+
+/// Rule built from a `[[rules]]` entry; its rows are collected into
+/// `RuleContext::custom_rows` before evaluation
+pub struct DeclarativeRule {
+    spec: DeclarativeRuleSpec,
+    condition: Option<Condition>,
+}
+
+impl DeclarativeRule {
+    pub fn new(spec: DeclarativeRuleSpec) -> Result<Self, String> {
+        let condition = spec
+            .condition
+            .as_deref()
+            .map(Condition::parse)
+            .transpose()?;
+        Ok(DeclarativeRule { spec, condition })
+    }
+
+    pub fn spec(&self) -> &DeclarativeRuleSpec {
+        &self.spec
+    }
+}
+
+impl Rule for DeclarativeRule {
+    fn id(&self) -> &str {
+        &self.spec.id
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Custom
+    }
+    fn description(&self) -> &str {
+        self.spec.description.as_deref().unwrap_or(&self.spec.title)
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Performance
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[]
+    }
+
+    /// A failed query says nothing about the rows it reported before
+    fn has_data(&self, context: &RuleContext) -> bool {
+        matches!(context.custom_rows.get(&self.spec.id), Some(Ok(_)))
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        let Some(rows) = context.custom_rows.get(&self.spec.id) else {
+            return Vec::new();
+        };
+
+        match rows {
+            Err(e) => vec![RuleIssue::Performance(PerformanceIssue {
+                issue_type: self.spec.title.clone(),
+                severity: IssueSeverity::Info,
+                description: format!("Custom rule {} failed", self.spec.id),
+                recommendation: "Fix the rule query in analyzer.toml".to_string(),
+                details: Some(e.clone()),
+                object: None,
+            })],
+            Ok(rows) => rows
+                .iter()
+                .filter(|row| self.condition.as_ref().is_none_or(|c| c.matches(row)))
+                .map(|row| {
+                    RuleIssue::Performance(PerformanceIssue {
+                        issue_type: self.spec.title.clone(),
+                        severity: self.spec.severity.clone(),
+                        description: render(&self.spec.message, row),
+                        recommendation: self
+                            .spec
+                            .recommendation
+                            .as_deref()
+                            .map(|r| render(r, row))
+                            .unwrap_or_default(),
+                        details: None,
+                        object: Some(match &self.spec.object {
+                            Some(object) => render(object, row),
+                            None => format!("row {}", stable_hash(&[&row.to_string()])),
+                        }),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+/// Comparisons of a column with a literal, joined by `and`
+#[derive(Debug, Clone)]
+struct Condition {
+    terms: Vec<(String, Comparison, Literal)>,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut terms = Vec::new();
+        for term in split_and(text) {
+            let term = term.trim();
+            let ops = [
+                (">=", Comparison::Ge),
+                ("<=", Comparison::Le),
+                ("!=", Comparison::Ne),
+                ("<>", Comparison::Ne),
+                ("=", Comparison::Eq),
+                (">", Comparison::Gt),
+                ("<", Comparison::Lt),
+            ];
+            let (position, symbol, op) = ops
+                .iter()
+                .filter_map(|(symbol, op)| term.find(symbol).map(|p| (p, *symbol, *op)))
+                .min_by_key(|(p, symbol, _)| (*p, usize::MAX - symbol.len()))
+                .ok_or_else(|| format!("no comparison operator in \"{}\"", term))?;
+
+            let column = term[..position].trim();
+            if column.is_empty() || !column.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(format!("invalid column name \"{}\"", column));
+            }
+
+            let value = term[position + symbol.len()..].trim();
+            let literal = if let Some(text) =
+                value.strip_prefix('\'').and_then(|v| v.strip_suffix('\''))
+            {
+                Literal::Text(text.replace("''", "'"))
+            } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+                Literal::Bool(value.eq_ignore_ascii_case("true"))
+            } else {
+                Literal::Number(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid value \"{}\"", value))?,
+                )
+            };
+
+            terms.push((column.to_string(), op, literal));
+        }
+
+        Ok(Condition { terms })
+    }
+
+    /// All terms hold; comparisons with missing or NULL columns do not
+    fn matches(&self, row: &Value) -> bool {
+        self.terms.iter().all(|(column, op, literal)| {
+            let Some(value) = row.get(column) else {
+                return false;
+            };
+            let ordering = match (literal, value) {
+                (Literal::Number(n), Value::Number(v)) => v.as_f64().and_then(|v| v.partial_cmp(n)),
+                (Literal::Number(n), Value::String(v)) => {
+                    v.parse::<f64>().ok().and_then(|v| v.partial_cmp(n))
+                }
+                (Literal::Text(t), Value::String(v)) => Some(v.as_str().cmp(t.as_str())),
+                (Literal::Text(t), Value::Number(v)) => Some(v.to_string().cmp(t)),
+                (Literal::Bool(b), Value::Bool(v)) => Some(v.cmp(b)),
+                _ => None,
+            };
+            let Some(ordering) = ordering else {
+                return false;
+            };
+            match op {
+                Comparison::Eq => ordering.is_eq(),
+                Comparison::Ne => ordering.is_ne(),
+                Comparison::Lt => ordering.is_lt(),
+                Comparison::Le => ordering.is_le(),
+                Comparison::Gt => ordering.is_gt(),
+                Comparison::Ge => ordering.is_ge(),
+            }
+        })
+    }
+}
+
+/// Split on the keyword `and` outside quoted strings
+fn split_and(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let words: Vec<&str> = text.split(' ').collect();
+
+    for word in words {
+        if !quoted && word.eq_ignore_ascii_case("and") {
+            terms.push(std::mem::take(&mut current));
+            continue;
+        }
+        quoted ^= word.matches('\'').count() % 2 == 1;
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    terms.push(current);
+    terms
+}
+
+/// Names of the `{column}` placeholders of a message template
+fn template_fields(template: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| "unclosed { in message".to_string())?;
+        let field = &rest[start + 1..start + end];
+        if field.is_empty() || !field.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("invalid placeholder {{{}}} in message", field));
+        }
+        fields.push(field.to_string());
+        rest = &rest[start + end + 1..];
+    }
+    Ok(fields)
+}
+
+/// Replace `{column}` placeholders with the row's values
+fn render(template: &str, row: &Value) -> String {
+    let mut text = template.to_string();
+    for field in template_fields(template).unwrap_or_default() {
+        let value = match row.get(&field) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) => "NULL".to_string(),
+            Some(v) => v.to_string(),
+            None => continue,
+        };
+        text = text.replace(&format!("{{{}}}", field), &value);
+    }
+    text
+}
@@ -0,0 +1,330 @@
+use super::connection::ConnectionManager;
+use super::history::unix_now;
+use super::storage;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendAction {
+    /// pg_cancel_backend: cancel the running query
+    Cancel,
+    /// pg_terminate_backend: close the session
+    Terminate,
+}
+
+impl BackendAction {
+    fn function(&self) -> &'static str {
+        match self {
+            BackendAction::Cancel => "pg_cancel_backend",
+            BackendAction::Terminate => "pg_terminate_backend",
+        }
+    }
+}
+
+/// Backend as the user saw it; used to make sure the pid was not reused
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendTarget {
+    pub pid: i32,
+    pub backend_start: String,
+    /// When set, the backend must still be running this exact query
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendActionResult {
+    pub pid: i32,
+    pub action: BackendAction,
+    pub success: bool,
+    pub message: String,
+}
+
+/// One entry of the local action audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    pub server_id: String,
+    pub server_name: Option<String>,
+    pub action: BackendAction,
+    pub pid: i32,
+    pub usename: Option<String>,
+    pub datname: Option<String>,
+    pub query: Option<String>,
+    pub backend_start: Option<String>,
+    pub success: bool,
+    pub message: String,
+}
+
+/// Session details fetched before signalling a backend
+struct BackendRow {
+    usename: Option<String>,
+    datname: Option<String>,
+    backend_start: Option<String>,
+    query: Option<String>,
+    /// The dashboard's own session, either this one or another pooled one
+    own_session: bool,
+}
+
+pub struct BackendController;
+
+impl BackendController {
+    /// Cancel or terminate one backend after checking it is still the
+    /// session the user selected
+    pub async fn signal(
+        manager: &ConnectionManager,
+        server_id: &str,
+        target: &BackendTarget,
+        action: BackendAction,
+    ) -> Result<BackendActionResult, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let row = client
+            .query_opt(
+                r#"
+                SELECT
+                    usename::text,
+                    datname::text,
+                    backend_start::text,
+                    query,
+                    pid = pg_backend_pid()
+                FROM pg_stat_activity
+                WHERE pid = $1
+                "#,
+                &[&target.pid],
+            )
+            .await
+            .map_err(|e| format!("Failed to look up backend: {}", e))?;
+
+        let backend = row.map(|row| {
+            let backend_start: Option<String> = row.get(2);
+            BackendRow {
+                usename: row.get(0),
+                datname: row.get(1),
+                own_session: row.get::<_, bool>(4)
+                    || backend_start
+                        .as_deref()
+                        .is_some_and(|start| manager.is_own_backend(server_id, target.pid, start)),
+                backend_start,
+                query: row.get(3),
+            }
+        });
+
+        let outcome = match &backend {
+            None => Err(format!("Backend {} no longer exists", target.pid)),
+            Some(backend) => match Self::check_target(backend, target) {
+                Err(e) => Err(e),
+                Ok(()) => {
+                    // Re-check backend_start in the same statement to close the
+                    // window between lookup and signal
+                    let sql = format!(
+                        r#"
+                        SELECT {}(pid)
+                        FROM pg_stat_activity
+                        WHERE pid = $1 AND backend_start::text = $2
+                        "#,
+                        action.function()
+                    );
+                    client
+                        .query_opt(&sql, &[&target.pid, &target.backend_start])
+                        .await
+                        .map_err(|e| format!("Failed to {} backend: {}", Self::verb(action), e))
+                        .and_then(|row| match row.map(|r| r.get::<_, bool>(0)) {
+                            Some(true) => Ok(()),
+                            Some(false) => {
+                                Err(format!("Backend {} did not accept the signal", target.pid))
+                            }
+                            None => Err(format!(
+                                "Backend {} exited before it was signalled",
+                                target.pid
+                            )),
+                        })
+                }
+            },
+        };
+
+        let result = BackendActionResult {
+            pid: target.pid,
+            action,
+            success: outcome.is_ok(),
+            message: match &outcome {
+                Ok(()) => format!("Backend {} {}", target.pid, Self::past_tense(action)),
+                Err(e) => e.clone(),
+            },
+        };
+
+        AuditLog::append(&AuditEntry {
+            timestamp: unix_now(),
+            server_id: server_id.to_string(),
+            server_name: manager.get_server_info(server_id).map(|s| s.name),
+            action,
+            pid: target.pid,
+            usename: backend.as_ref().and_then(|b| b.usename.clone()),
+            datname: backend.as_ref().and_then(|b| b.datname.clone()),
+            query: backend.as_ref().and_then(|b| b.query.clone()),
+            backend_start: backend.as_ref().and_then(|b| b.backend_start.clone()),
+            success: result.success,
+            message: result.message.clone(),
+        })?;
+
+        Ok(result)
+    }
+
+    /// Terminate every idle-in-transaction session older than the given age
+    pub async fn terminate_idle_in_transaction(
+        manager: &ConnectionManager,
+        server_id: &str,
+        min_idle_minutes: i32,
+    ) -> Result<Vec<BackendActionResult>, String> {
+        if min_idle_minutes <= 0 {
+            return Err("Idle time must be at least 1 minute".to_string());
+        }
+
+        let targets: Vec<BackendTarget> = {
+            let client = manager.get_client(server_id).await?;
+            let rows = client
+                .query(
+                    r#"
+                    SELECT pid, backend_start::text, query
+                    FROM pg_stat_activity
+                    WHERE state IN ('idle in transaction', 'idle in transaction (aborted)')
+                    AND state_change < now() - make_interval(mins => $1)
+                    AND pid != pg_backend_pid()
+                    ORDER BY state_change
+                    "#,
+                    &[&min_idle_minutes],
+                )
+                .await
+                .map_err(|e| format!("Failed to query idle sessions: {}", e))?;
+
+            rows.iter()
+                .map(|row| BackendTarget {
+                    pid: row.get(0),
+                    backend_start: row.get(1),
+                    query: row.get(2),
+                })
+                .filter(|target| {
+                    !manager.is_own_backend(server_id, target.pid, &target.backend_start)
+                })
+                .collect()
+        };
+
+        let mut results = Vec::new();
+        for target in &targets {
+            let action = BackendAction::Terminate;
+            results.push(
+                match Self::signal(manager, server_id, target, action).await {
+                    Ok(result) => result,
+                    Err(e) => BackendActionResult {
+                        pid: target.pid,
+                        action,
+                        success: false,
+                        message: e,
+                    },
+                },
+            );
+        }
+
+        Ok(results)
+    }
+
+    fn check_target(backend: &BackendRow, target: &BackendTarget) -> Result<(), String> {
+        if backend.own_session {
+            return Err(format!(
+                "Backend {} is a dashboard monitoring connection",
+                target.pid
+            ));
+        }
+
+        if backend.backend_start.as_deref() != Some(target.backend_start.as_str()) {
+            return Err(format!(
+                "Backend {} belongs to a different session now (pid was reused)",
+                target.pid
+            ));
+        }
+
+        if let Some(query) = &target.query {
+            if backend.query.as_ref() != Some(query) {
+                return Err(format!(
+                    "Backend {} is no longer running the selected query",
+                    target.pid
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn verb(action: BackendAction) -> &'static str {
+        match action {
+            BackendAction::Cancel => "cancel",
+            BackendAction::Terminate => "terminate",
+        }
+    }
+
+    fn past_tense(action: BackendAction) -> &'static str {
+        match action {
+            BackendAction::Cancel => "cancelled",
+            BackendAction::Terminate => "terminated",
+        }
+    }
+}
+
+/// Append-only JSON lines log of backend actions in the app data dir
+pub struct AuditLog;
+
+impl AuditLog {
+    fn path() -> Option<PathBuf> {
+        storage::data_dir().map(|p| p.join("audit.jsonl"))
+    }
+
+    pub fn append(entry: &AuditEntry) -> Result<(), String> {
+        let Some(path) = Self::path() else {
+            return Err("Could not determine data directory".to_string());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+
+        let line = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open audit log: {}", e))?;
+
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write audit log: {}", e))
+    }
+
+    /// Most recent entries first, optionally for one server
+    pub fn list(server_id: Option<&str>, limit: usize) -> Result<Vec<AuditEntry>, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Vec::new());
+        };
+
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to open audit log: {}", e)),
+        };
+
+        let mut entries: Vec<AuditEntry> = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .filter(|entry: &AuditEntry| server_id.is_none_or(|id| entry.server_id == id))
+            .collect();
+
+        entries.reverse();
+        entries.truncate(limit);
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,417 @@
+use super::analyzer::{IssueSeverity, PerformanceIssue};
+use super::connection::ConnectionManager;
+use serde::{Deserialize, Serialize};
+
+/// Relations smaller than this are never reported as bloated
+const MIN_WASTED_BYTES: i64 = 100 * 1024 * 1024;
+const WARNING_RATIO: f64 = 50.0;
+const CRITICAL_RATIO: f64 = 80.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BloatSource {
+    /// Statistics-based estimate from pg_stats
+    Estimate,
+    /// Measured with pgstattuple
+    Pgstattuple,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationBloat {
+    #[serde(skip)]
+    oid: u32,
+    pub schemaname: String,
+    pub table_name: String,
+    /// Set for index bloat
+    pub index_name: Option<String>,
+    pub real_size_bytes: i64,
+    /// Space beyond what the live rows need at the relation's fillfactor
+    pub wasted_bytes: i64,
+    /// Wasted share of the relation size (0-100)
+    pub bloat_ratio: f64,
+    pub fillfactor: i32,
+    /// Estimate is unreliable: columns without statistics or of type `name`
+    pub stats_missing: bool,
+    pub source: BloatSource,
+    /// Why pgstattuple could not measure the relation; the estimate is kept
+    pub exact_error: Option<String>,
+}
+
+pub struct BloatCollector;
+
+impl BloatCollector {
+    /// Most bloated tables of the connected database. With `exact`, the
+    /// listed tables are measured with pgstattuple_approx when installed;
+    /// a table it fails on keeps its estimate.
+    pub async fn get_table_bloat(
+        manager: &ConnectionManager,
+        server_id: &str,
+        limit: i32,
+        exact: bool,
+    ) -> Result<Vec<RelationBloat>, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let query = r#"
+            SELECT
+                tblid, schemaname::text, tblname::text,
+                (bs * tblpages)::bigint,
+                CASE WHEN tblpages - est_tblpages_ff > 0
+                    THEN ((tblpages - est_tblpages_ff) * bs)::bigint ELSE 0 END,
+                CASE WHEN tblpages > 0 AND tblpages - est_tblpages_ff > 0
+                    THEN (100 * (tblpages - est_tblpages_ff) / tblpages)::float8 ELSE 0 END,
+                fillfactor::int,
+                is_na
+            FROM (
+                SELECT
+                    ceil(reltuples / ((bs - page_hdr) * fillfactor / (tpl_size * 100)))
+                        + ceil(toasttuples / 4) AS est_tblpages_ff,
+                    tblpages, fillfactor, bs, tblid, schemaname, tblname, is_na
+                FROM (
+                    SELECT
+                        (4 + tpl_hdr_size + tpl_data_size + (2 * ma)
+                            - CASE WHEN tpl_hdr_size % ma = 0 THEN ma ELSE tpl_hdr_size % ma END
+                            - CASE WHEN ceil(tpl_data_size)::int % ma = 0 THEN ma
+                                   ELSE ceil(tpl_data_size)::int % ma END
+                        ) AS tpl_size,
+                        (heappages + toastpages) AS tblpages,
+                        reltuples, toasttuples, bs, page_hdr, tblid, schemaname, tblname,
+                        fillfactor, is_na
+                    FROM (
+                        SELECT
+                            tbl.oid AS tblid, ns.nspname AS schemaname, tbl.relname AS tblname,
+                            tbl.reltuples, tbl.relpages AS heappages,
+                            coalesce(toast.relpages, 0) AS toastpages,
+                            coalesce(toast.reltuples, 0) AS toasttuples,
+                            coalesce(substring(array_to_string(tbl.reloptions, ' ')
+                                FROM 'fillfactor=([0-9]+)')::smallint, 100) AS fillfactor,
+                            current_setting('block_size')::numeric AS bs,
+                            CASE WHEN version() ~ 'mingw32|64-bit|x86_64|ppc64|ia64|amd64'
+                                THEN 8 ELSE 4 END AS ma,
+                            24 AS page_hdr,
+                            23 + CASE WHEN max(coalesce(s.null_frac, 0)) > 0
+                                     THEN (7 + count(s.attname)) / 8 ELSE 0::int END
+                                AS tpl_hdr_size,
+                            sum((1 - coalesce(s.null_frac, 0)) * coalesce(s.avg_width, 0))
+                                AS tpl_data_size,
+                            bool_or(att.atttypid = 'pg_catalog.name'::regtype)
+                                OR sum(CASE WHEN att.attnum > 0 THEN 1 ELSE 0 END)
+                                    <> count(s.attname) AS is_na
+                        FROM pg_attribute att
+                        JOIN pg_class tbl ON att.attrelid = tbl.oid
+                        JOIN pg_namespace ns ON ns.oid = tbl.relnamespace
+                        LEFT JOIN pg_stats s ON s.schemaname = ns.nspname
+                            AND s.tablename = tbl.relname
+                            AND s.inherited = false
+                            AND s.attname = att.attname
+                        LEFT JOIN pg_class toast ON tbl.reltoastrelid = toast.oid
+                        WHERE NOT att.attisdropped
+                            AND att.attnum > 0
+                            AND tbl.relkind IN ('r', 'm')
+                            AND tbl.reltuples >= 0
+                            AND ns.nspname NOT IN ('pg_catalog', 'information_schema')
+                        GROUP BY 1, 2, 3, 4, 5, 6, 7, 8, 9, 10
+                    ) AS s
+                ) AS s2
+            ) AS s3
+            ORDER BY 5 DESC
+            LIMIT $1
+        "#;
+
+        let rows = client
+            .query(query, &[&(limit as i64)])
+            .await
+            .map_err(|e| format!("Failed to estimate table bloat: {}", e))?;
+
+        let mut bloat: Vec<RelationBloat> = rows
+            .iter()
+            .map(|row| RelationBloat {
+                oid: row.get(0),
+                schemaname: row.get(1),
+                table_name: row.get(2),
+                index_name: None,
+                real_size_bytes: row.get(3),
+                wasted_bytes: row.get(4),
+                bloat_ratio: row.get(5),
+                fillfactor: row.get(6),
+                stats_missing: row.get(7),
+                source: BloatSource::Estimate,
+                exact_error: None,
+            })
+            .collect();
+
+        if exact && Self::has_pgstattuple(&client).await? {
+            for relation in &mut bloat {
+                let row = match client
+                    .query_one(
+                        "SELECT table_len, dead_tuple_len + approx_free_space FROM pgstattuple_approx($1::oid::regclass)",
+                        &[&relation.oid],
+                    )
+                    .await
+                {
+                    Ok(row) => row,
+                    Err(e) => {
+                        relation.exact_error =
+                            Some(format!("Failed to run pgstattuple_approx: {}", e));
+                        continue;
+                    }
+                };
+
+                let size: i64 = row.get(0);
+                let free: i64 = row.get(1);
+                // Free space the fillfactor reserves on purpose is not bloat
+                let reserved = size as f64 * (100 - relation.fillfactor) as f64 / 100.0;
+                relation.set_exact(size, (free as f64 - reserved).max(0.0) as i64);
+            }
+        }
+
+        Ok(bloat)
+    }
+
+    /// Most bloated btree indexes of the connected database. With `exact`,
+    /// the listed indexes are measured with pgstatindex when installed; an
+    /// index it fails on keeps its estimate.
+    pub async fn get_index_bloat(
+        manager: &ConnectionManager,
+        server_id: &str,
+        limit: i32,
+        exact: bool,
+    ) -> Result<Vec<RelationBloat>, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let query = r#"
+            SELECT
+                idxoid, nspname::text, tblname::text, idxname::text,
+                (bs * relpages)::bigint,
+                CASE WHEN relpages > est_pages_ff
+                    THEN (bs * (relpages - est_pages_ff))::bigint ELSE 0 END,
+                CASE WHEN relpages > est_pages_ff
+                    THEN (100 * (relpages - est_pages_ff)::float8 / relpages) ELSE 0 END,
+                fillfactor::int,
+                is_na
+            FROM (
+                SELECT
+                    coalesce(1 + ceil(reltuples / floor((bs - pageopqdata - pagehdr) * fillfactor
+                        / (100 * (4 + nulldatahdrwidth)::float8))), 0) AS est_pages_ff,
+                    bs, nspname, tblname, idxname, idxoid, relpages, fillfactor, is_na
+                FROM (
+                    SELECT
+                        maxalign, bs, nspname, tblname, idxname, reltuples, relpages, idxoid,
+                        fillfactor,
+                        (index_tuple_hdr_bm
+                            + maxalign - CASE WHEN index_tuple_hdr_bm % maxalign = 0 THEN maxalign
+                                              ELSE index_tuple_hdr_bm % maxalign END
+                            + nulldatawidth + maxalign - CASE
+                                WHEN nulldatawidth = 0 THEN 0
+                                WHEN nulldatawidth::integer % maxalign = 0 THEN maxalign
+                                ELSE nulldatawidth::integer % maxalign END
+                        )::numeric AS nulldatahdrwidth,
+                        pagehdr, pageopqdata, is_na
+                    FROM (
+                        SELECT
+                            n.nspname, i.tblname, i.idxname, i.reltuples, i.relpages, i.idxoid,
+                            i.fillfactor,
+                            current_setting('block_size')::numeric AS bs,
+                            CASE WHEN version() ~ 'mingw32|64-bit|x86_64|ppc64|ia64|amd64'
+                                THEN 8 ELSE 4 END AS maxalign,
+                            24 AS pagehdr,
+                            16 AS pageopqdata,
+                            CASE WHEN max(coalesce(s.null_frac, 0)) = 0 THEN 8
+                                 ELSE 8 + ((32 + 8 - 1) / 8) END AS index_tuple_hdr_bm,
+                            sum((1 - coalesce(s.null_frac, 0)) * coalesce(s.avg_width, 1024))
+                                AS nulldatawidth,
+                            max(CASE WHEN i.atttypid = 'pg_catalog.name'::regtype THEN 1 ELSE 0 END) > 0
+                                AS is_na
+                        FROM (
+                            SELECT
+                                ct.relname AS tblname, ct.relnamespace, ic.idxname, ic.attpos,
+                                ic.reltuples, ic.relpages, ic.tbloid, ic.idxoid, ic.fillfactor,
+                                coalesce(a1.attnum, a2.attnum) AS attnum,
+                                coalesce(a1.attname, a2.attname) AS attname,
+                                coalesce(a1.atttypid, a2.atttypid) AS atttypid,
+                                CASE WHEN a1.attnum IS NULL THEN ic.idxname ELSE ct.relname END
+                                    AS attrelname
+                            FROM (
+                                SELECT
+                                    idxname, reltuples, relpages, tbloid, idxoid, fillfactor,
+                                    indkey, generate_series(1, indnatts) AS attpos
+                                FROM (
+                                    SELECT
+                                        ci.relname AS idxname, ci.reltuples, ci.relpages,
+                                        i.indrelid AS tbloid, i.indexrelid AS idxoid,
+                                        coalesce(substring(array_to_string(ci.reloptions, ' ')
+                                            FROM 'fillfactor=([0-9]+)')::smallint, 90) AS fillfactor,
+                                        i.indnatts,
+                                        string_to_array(textin(int2vectorout(i.indkey)), ' ')::int[]
+                                            AS indkey
+                                    FROM pg_index i
+                                    JOIN pg_class ci ON ci.oid = i.indexrelid
+                                    JOIN pg_namespace cn ON cn.oid = ci.relnamespace
+                                    WHERE ci.relam = (SELECT oid FROM pg_am WHERE amname = 'btree')
+                                        AND ci.relpages > 0
+                                        AND cn.nspname NOT IN ('pg_catalog', 'information_schema')
+                                ) AS idx_data
+                            ) AS ic
+                            JOIN pg_class ct ON ct.oid = ic.tbloid
+                            LEFT JOIN pg_attribute a1 ON ic.indkey[ic.attpos] <> 0
+                                AND a1.attrelid = ic.tbloid
+                                AND a1.attnum = ic.indkey[ic.attpos]
+                            LEFT JOIN pg_attribute a2 ON ic.indkey[ic.attpos] = 0
+                                AND a2.attrelid = ic.idxoid
+                                AND a2.attnum = ic.attpos
+                        ) i
+                        JOIN pg_namespace n ON n.oid = i.relnamespace
+                        JOIN pg_stats s ON s.schemaname = n.nspname
+                            AND s.tablename = i.attrelname
+                            AND s.attname = i.attname
+                        GROUP BY 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11
+                    ) AS rows_data_stats
+                ) AS rows_hdr_pdg_stats
+            ) AS relation_stats
+            ORDER BY 6 DESC
+            LIMIT $1
+        "#;
+
+        let rows = client
+            .query(query, &[&(limit as i64)])
+            .await
+            .map_err(|e| format!("Failed to estimate index bloat: {}", e))?;
+
+        let mut bloat: Vec<RelationBloat> = rows
+            .iter()
+            .map(|row| RelationBloat {
+                oid: row.get(0),
+                schemaname: row.get(1),
+                table_name: row.get(2),
+                index_name: row.get(3),
+                real_size_bytes: row.get(4),
+                wasted_bytes: row.get(5),
+                bloat_ratio: row.get(6),
+                fillfactor: row.get(7),
+                stats_missing: row.get(8),
+                source: BloatSource::Estimate,
+                exact_error: None,
+            })
+            .collect();
+
+        if exact && Self::has_pgstattuple(&client).await? {
+            for relation in &mut bloat {
+                let row = match client
+                    .query_one(
+                        "SELECT index_size, avg_leaf_density FROM pgstatindex($1::oid::regclass)",
+                        &[&relation.oid],
+                    )
+                    .await
+                {
+                    Ok(row) => row,
+                    Err(e) => {
+                        relation.exact_error = Some(format!("Failed to run pgstatindex: {}", e));
+                        continue;
+                    }
+                };
+
+                let size: i64 = row.get(0);
+                let density: f64 = row.get(1);
+                if density.is_nan() {
+                    continue;
+                }
+                // Leaf pages are expected to be filled up to the fillfactor
+                let wasted = size as f64 * (1.0 - density / relation.fillfactor as f64);
+                relation.set_exact(size, wasted.max(0.0) as i64);
+            }
+        }
+
+        Ok(bloat)
+    }
+
+    async fn has_pgstattuple(client: &tokio_postgres::Client) -> Result<bool, String> {
+        let rows = client
+            .query(
+                "SELECT 1 FROM pg_extension WHERE extname = 'pgstattuple'",
+                &[],
+            )
+            .await
+            .map_err(|e| format!("Failed to check pgstattuple: {}", e))?;
+
+        Ok(!rows.is_empty())
+    }
+
+    /// Bloat rules for `ConfigAnalyzer::detect_performance_issues`
+    pub fn issues(tables: &[RelationBloat], indexes: &[RelationBloat]) -> Vec<PerformanceIssue> {
+        let mut issues = Vec::new();
+
+        for relation in tables.iter().chain(indexes) {
+            let Some(severity) = Self::severity(relation) else {
+                continue;
+            };
+
+            let (issue_type, name, recommendation) = match &relation.index_name {
+                Some(index) => (
+                    "Index Bloat",
+                    format!("Index {}.{}", relation.schemaname, index),
+                    format!(
+                        "REINDEX INDEX CONCURRENTLY {}.{};",
+                        relation.schemaname, index
+                    ),
+                ),
+                None => (
+                    "Table Bloat",
+                    format!("Table {}.{}", relation.schemaname, relation.table_name),
+                    "Rewrite the table with pg_repack (or VACUUM FULL during a maintenance window) and check autovacuum settings".to_string(),
+                ),
+            };
+
+            issues.push(PerformanceIssue {
+                issue_type: issue_type.to_string(),
+                severity,
+                description: format!(
+                    "{} wastes {} MB ({:.1}% of {} MB)",
+                    name,
+                    relation.wasted_bytes / 1024 / 1024,
+                    relation.bloat_ratio,
+                    relation.real_size_bytes / 1024 / 1024
+                ),
+                recommendation,
+                details: Some(match (relation.source, &relation.exact_error) {
+                    (BloatSource::Estimate, None) => "Estimated from pg_stats".to_string(),
+                    (BloatSource::Estimate, Some(e)) => {
+                        format!("Estimated from pg_stats ({})", e)
+                    }
+                    (BloatSource::Pgstattuple, _) => "Measured with pgstattuple".to_string(),
+                }),
+                object: Some(format!(
+                    "{}.{}",
+                    relation.schemaname,
+                    relation.index_name.as_ref().unwrap_or(&relation.table_name)
+                )),
+            });
+        }
+
+        issues
+    }
+
+    fn severity(relation: &RelationBloat) -> Option<IssueSeverity> {
+        if relation.stats_missing || relation.wasted_bytes < MIN_WASTED_BYTES {
+            None
+        } else if relation.bloat_ratio >= CRITICAL_RATIO {
+            Some(IssueSeverity::Critical)
+        } else if relation.bloat_ratio >= WARNING_RATIO {
+            Some(IssueSeverity::Warning)
+        } else {
+            None
+        }
+    }
+}
+
+impl RelationBloat {
+    fn set_exact(&mut self, size: i64, wasted: i64) {
+        self.real_size_bytes = size;
+        self.wasted_bytes = wasted.min(size);
+        self.bloat_ratio = if size > 0 {
+            self.wasted_bytes as f64 / size as f64 * 100.0
+        } else {
+            0.0
+        };
+        self.stats_missing = false;
+        self.source = BloatSource::Pgstattuple;
+    }
+}
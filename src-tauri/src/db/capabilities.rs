@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// Server features derived from `server_version_num`, detected once per
+/// connection and cached by `ConnectionManager`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerCapabilities {
+    pub version_num: i32,
+}
+
+impl ServerCapabilities {
+    pub fn new(version_num: i32) -> Self {
+        ServerCapabilities { version_num }
+    }
+
+    /// PG17 moved checkpoint counters from pg_stat_bgwriter to pg_stat_checkpointer
+    pub fn has_stat_checkpointer(&self) -> bool {
+        self.version_num >= 170000
+    }
+
+    /// pg_stat_io exists since PG16
+    pub fn has_stat_io(&self) -> bool {
+        self.version_num >= 160000
+    }
+}
+
+/// Installed extension version from `pg_extension.extversion`, e.g. "1.10"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ExtensionVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ExtensionVersion {
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map(|m| m.parse().ok()).unwrap_or(Some(0))?;
+        Some(ExtensionVersion { major, minor })
+    }
+
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+}
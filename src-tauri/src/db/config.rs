@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use super::connection::ConnectionManager;
+use super::settings::{ServerSettings, TypedSetting};
+use super::storage::{self, HardwareProfile};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostgresConfig {
@@ -13,12 +15,31 @@ pub struct PostgresConfig {
     pub max_val: Option<String>,
 }
 
+/// Where a hardware value came from, most trustworthy first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HardwareSource {
+    /// Entered by the user for this server
+    Profile,
+    /// system_stats extension functions
+    SystemStats,
+    /// /proc/meminfo or /proc/cpuinfo read with pg_read_file
+    ProcFs,
+    /// Build target in version()
+    Version,
+    /// Guessed from settings
+    Estimate,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HardwareInfo {
     pub cpu_cores: i32,
     pub total_memory_mb: i64,
     pub postgres_version: String,
     pub os_type: String,
+    pub cpu_cores_source: HardwareSource,
+    pub total_memory_source: HardwareSource,
+    pub os_type_source: HardwareSource,
 }
 
 pub struct ConfigCollector;
@@ -66,6 +87,32 @@ impl ConfigCollector {
         Ok(settings)
     }
 
+    /// All settings with values normalized to bytes and milliseconds
+    pub async fn get_typed_settings(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<ServerSettings, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let query = r#"
+            SELECT name, setting, unit, vartype, enumvals, category, source, context
+            FROM pg_settings
+            ORDER BY category, name
+        "#;
+
+        let rows = client
+            .query(query, &[])
+            .await
+            .map_err(|e| format!("Failed to query settings: {}", e))?;
+
+        Ok(ServerSettings::new(
+            rows.iter().map(TypedSetting::from_row).collect(),
+        ))
+    }
+
+    /// Host sizing from the stored profile, system_stats, /proc or, failing
+    /// those, estimates from settings. pg_stat_kcache only reports CPU time
+    /// per statement, so it does not help here.
     pub async fn get_hardware_info(
         manager: &ConnectionManager,
         server_id: &str,
@@ -79,32 +126,173 @@ impl ConfigCollector {
             .map_err(|e| format!("Failed to get version: {}", e))?;
         let version: String = version_row.get(0);
 
-        // Get CPU cores from max_worker_processes setting (better estimate than active connections)
-        let cpu_query = "SELECT setting::int FROM pg_settings WHERE name = 'max_worker_processes'";
-        let cpu_row = client.query_opt(cpu_query, &[]).await;
-        let estimated_cores: i64 = match cpu_row {
-            Ok(Some(row)) => row.get::<_, i32>(0) as i64,
-            _ => 8, // Default fallback
-        };
+        let profile = storage::get_hardware_profile(server_id).unwrap_or_default();
+        let detected_os = Self::os_from_version(&version);
 
-        // Try to estimate memory from settings
-        let mem_query = "SELECT setting::bigint * 8 / 1024 FROM pg_settings WHERE name = 'shared_buffers'";
-        let mem_row = client.query_one(mem_query, &[]).await;
+        let mut cpu: Option<(i32, HardwareSource)> =
+            profile.cpu_cores.map(|c| (c, HardwareSource::Profile));
+        let mut memory: Option<(i64, HardwareSource)> =
+            profile.total_memory_mb.map(|m| (m, HardwareSource::Profile));
 
-        let total_memory_mb = match mem_row {
-            Ok(row) => {
-                let shared_buffers_mb: i64 = row.get(0);
-                // Estimate total memory as 4x shared_buffers (rough heuristic)
-                shared_buffers_mb * 4
+        if cpu.is_none() || memory.is_none() {
+            let (stats_cpu, stats_memory) = Self::read_system_stats(&client).await;
+            cpu = cpu.or(stats_cpu.map(|c| (c, HardwareSource::SystemStats)));
+            memory = memory.or(stats_memory.map(|m| (m, HardwareSource::SystemStats)));
+        }
+
+        if (cpu.is_none() || memory.is_none()) && detected_os.as_deref() == Some("Linux") {
+            let (proc_cpu, proc_memory) = Self::read_proc(&client).await;
+            cpu = cpu.or(proc_cpu.map(|c| (c, HardwareSource::ProcFs)));
+            memory = memory.or(proc_memory.map(|m| (m, HardwareSource::ProcFs)));
+        }
+
+        let (cpu_cores, cpu_cores_source) = match cpu {
+            Some(cpu) => cpu,
+            None => {
+                // Get CPU cores from max_worker_processes setting (better estimate than active connections)
+                let cpu_query = "SELECT setting::int FROM pg_settings WHERE name = 'max_worker_processes'";
+                let estimated_cores = match client.query_opt(cpu_query, &[]).await {
+                    Ok(Some(row)) => row.get::<_, i32>(0),
+                    _ => 8, // Default fallback
+                };
+                (estimated_cores, HardwareSource::Estimate)
+            }
+        };
+
+        let (total_memory_mb, total_memory_source) = match memory {
+            Some(memory) => memory,
+            None => {
+                // Try to estimate memory from settings
+                let mem_query = "SELECT setting::bigint * 8 / 1024 FROM pg_settings WHERE name = 'shared_buffers'";
+                let estimated_mb = match client.query_one(mem_query, &[]).await {
+                    Ok(row) => {
+                        let shared_buffers_mb: i64 = row.get(0);
+                        // Estimate total memory as 4x shared_buffers (rough heuristic)
+                        shared_buffers_mb * 4
+                    }
+                    Err(_) => 8192, // Default to 8GB if can't determine
+                };
+                (estimated_mb, HardwareSource::Estimate)
             }
-            Err(_) => 8192, // Default to 8GB if can't determine
+        };
+
+        let (os_type, os_type_source) = match (profile.os_type, detected_os) {
+            (Some(os), _) => (os, HardwareSource::Profile),
+            (None, Some(os)) => (os, HardwareSource::Version),
+            (None, None) => ("Unknown".to_string(), HardwareSource::Estimate),
         };
 
         Ok(HardwareInfo {
-            cpu_cores: estimated_cores.max(1) as i32,
+            cpu_cores: cpu_cores.max(1),
             total_memory_mb,
             postgres_version: version,
-            os_type: "Linux".to_string(), // Simplified
+            os_type,
+            cpu_cores_source,
+            total_memory_source,
+            os_type_source,
         })
     }
+
+    pub fn get_hardware_profile(server_id: &str) -> Option<HardwareProfile> {
+        storage::get_hardware_profile(server_id)
+    }
+
+    pub fn set_hardware_profile(
+        server_id: &str,
+        profile: Option<HardwareProfile>,
+    ) -> Result<(), String> {
+        storage::set_hardware_profile(server_id, profile)
+    }
+
+    /// OS family from the build target in version(), e.g.
+    /// "PostgreSQL 16.2 on x86_64-pc-linux-gnu, compiled by gcc ..."
+    fn os_from_version(version: &str) -> Option<String> {
+        let target = version.split(" on ").nth(1)?.split(',').next()?.to_lowercase();
+        let os = if target.contains("linux") {
+            "Linux"
+        } else if target.contains("darwin") || target.contains("apple") {
+            "macOS"
+        } else if target.contains("mingw") || target.contains("windows") || target.contains("msvc") {
+            "Windows"
+        } else if target.contains("freebsd") {
+            "FreeBSD"
+        } else if target.contains("openbsd") {
+            "OpenBSD"
+        } else if target.contains("netbsd") {
+            "NetBSD"
+        } else if target.contains("solaris") || target.contains("illumos") {
+            "Solaris"
+        } else {
+            return None;
+        };
+        Some(os.to_string())
+    }
+
+    /// Logical CPUs and total memory (MB) from the system_stats extension
+    async fn read_system_stats(client: &tokio_postgres::Client) -> (Option<i32>, Option<i64>) {
+        let installed = client
+            .query_opt("SELECT 1 FROM pg_extension WHERE extname = 'system_stats'", &[])
+            .await
+            .ok()
+            .flatten()
+            .is_some();
+        if !installed {
+            return (None, None);
+        }
+
+        let cpu = client
+            .query_one(
+                "SELECT COALESCE(NULLIF(max(logical_processor), 0), max(no_of_cores))::int FROM pg_sys_cpu_info()",
+                &[],
+            )
+            .await
+            .ok()
+            .and_then(|row| row.get::<_, Option<i32>>(0))
+            .filter(|cores| *cores > 0);
+
+        let memory = client
+            .query_one(
+                "SELECT (max(total_memory) / 1024 / 1024)::bigint FROM pg_sys_memory_info()",
+                &[],
+            )
+            .await
+            .ok()
+            .and_then(|row| row.get::<_, Option<i64>>(0))
+            .filter(|mb| *mb > 0);
+
+        (cpu, memory)
+    }
+
+    /// Logical CPUs and total memory (MB) from /proc; needs superuser or
+    /// pg_read_server_files. Explicit lengths because /proc files report a
+    /// size of zero.
+    async fn read_proc(client: &tokio_postgres::Client) -> (Option<i32>, Option<i64>) {
+        let read = |path: &'static str, length: i64| async move {
+            client
+                .query_one("SELECT pg_read_file($1, 0, $2)", &[&path, &length])
+                .await
+                .ok()
+                .map(|row| row.get::<_, String>(0))
+        };
+
+        let cpu = read("/proc/cpuinfo", 4 * 1024 * 1024)
+            .await
+            .map(|cpuinfo| {
+                cpuinfo
+                    .lines()
+                    .filter(|line| line.starts_with("processor"))
+                    .count() as i32
+            })
+            .filter(|cores| *cores > 0);
+
+        let memory = read("/proc/meminfo", 64 * 1024).await.and_then(|meminfo| {
+            meminfo
+                .lines()
+                .find_map(|line| line.strip_prefix("MemTotal:"))
+                .and_then(|rest| rest.trim().trim_end_matches("kB").trim().parse::<i64>().ok())
+                .map(|kb| kb / 1024)
+        });
+
+        (cpu, memory)
+    }
 }
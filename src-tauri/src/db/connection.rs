@@ -1,16 +1,35 @@
+use crate::db::capabilities::ServerCapabilities;
 use crate::db::types::{ConnectionConfig, ConnectionTestResult, ServerInfo};
 use crate::db::storage::{self, SavedServerConfig};
-use deadpool_postgres::{Config, ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime, Timeouts};
-use std::collections::HashMap;
+use crate::db::tls;
+use crate::db::tunnel::SshTunnel;
+use deadpool_postgres::{
+    Config, Hook, HookError, ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime, Timeouts,
+};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio_postgres::NoTls;
 
+/// application_name of the dashboard's own pooled connections, so they are
+/// recognizable in pg_stat_activity
+pub const MONITOR_APPLICATION_NAME: &str = "pg-dashboard";
+
+/// Sessions opened by the pools as (server id, pid, backend_start); the
+/// start time tells a reused pid apart
+type OwnBackends = Arc<Mutex<HashSet<(String, i32, String)>>>;
+
+#[derive(Clone)]
 pub struct ConnectionManager {
     pools: Arc<Mutex<HashMap<String, Pool>>>,
     configs: Arc<Mutex<HashMap<String, ConnectionConfig>>>,
+    /// SSH tunnels backing pools of servers behind a jump host
+    tunnels: Arc<Mutex<HashMap<String, SshTunnel>>>,
+    /// Server version capabilities, detected once per connection
+    capabilities: Arc<Mutex<HashMap<String, ServerCapabilities>>>,
     /// Saved server configs (persisted to disk)
     saved_servers: Arc<Mutex<HashMap<String, SavedServerConfig>>>,
+    own_backends: OwnBackends,
 }
 
 impl ConnectionManager {
@@ -25,17 +44,55 @@ impl ConnectionManager {
         ConnectionManager {
             pools: Arc::new(Mutex::new(HashMap::new())),
             configs: Arc::new(Mutex::new(HashMap::new())),
+            tunnels: Arc::new(Mutex::new(HashMap::new())),
+            capabilities: Arc::new(Mutex::new(HashMap::new())),
             saved_servers: Arc::new(Mutex::new(saved_map)),
+            own_backends: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 
     pub async fn test_connection(&self, config: &ConnectionConfig) -> ConnectionTestResult {
-        let conn_string = format!(
-            "host={} port={} dbname={} user={} password={}",
-            config.host, config.port, config.database, config.username, config.password
-        );
+        let ssl_mode = config.effective_ssl_mode();
+
+        let connector = match tls::make_tls_connector(config) {
+            Ok(connector) => connector,
+            Err(e) => {
+                return ConnectionTestResult {
+                    success: false,
+                    message: format!("Connection failed: {}", e),
+                    postgres_version: None,
+                }
+            }
+        };
+
+        // Kept alive until the test finishes
+        let tunnel = match Self::open_tunnel(config).await {
+            Ok(tunnel) => tunnel,
+            Err(e) => {
+                return ConnectionTestResult {
+                    success: false,
+                    message: format!("Connection failed: {}", e),
+                    postgres_version: None,
+                }
+            }
+        };
+
+        let mut pg_config = tokio_postgres::Config::new();
+        pg_config
+            .host(&config.host)
+            .port(config.port)
+            .dbname(&config.database)
+            .user(&config.username)
+            .password(&config.password)
+            .ssl_mode(tls::pool_ssl_mode(ssl_mode).into());
+
+        if let Some(tunnel) = &tunnel {
+            pg_config
+                .hostaddr(IpAddr::V4(Ipv4Addr::LOCALHOST))
+                .port(tunnel.local_port());
+        }
 
-        match tokio_postgres::connect(&conn_string, NoTls).await {
+        match pg_config.connect(connector).await {
             Ok((client, connection)) => {
                 // Spawn connection handler
                 tokio::spawn(async move {
@@ -65,19 +122,52 @@ impl ConnectionManager {
             }
             Err(e) => ConnectionTestResult {
                 success: false,
-                message: format!("Connection failed: {}", e),
+                message: format!(
+                    "Connection failed: {}",
+                    tls::describe_error(ssl_mode, &e.to_string())
+                ),
                 postgres_version: None,
             },
         }
     }
 
-    pub async fn add_connection(&self, config: ConnectionConfig) -> Result<(), String> {
+    /// Open the SSH tunnel for a config, if it has one
+    async fn open_tunnel(config: &ConnectionConfig) -> Result<Option<SshTunnel>, String> {
+        let Some(ssh) = config.ssh_tunnel.clone() else {
+            return Ok(None);
+        };
+
+        let host = config.host.clone();
+        let port = config.port;
+
+        tokio::task::spawn_blocking(move || SshTunnel::open(&ssh, &host, port))
+            .await
+            .map_err(|e| format!("SSH tunnel task failed: {}", e))?
+            .map(Some)
+    }
+
+    /// Build a connection pool for the given config and check it can connect.
+    /// The returned tunnel (if any) must live as long as the pool.
+    async fn create_pool(
+        &self,
+        config: &ConnectionConfig,
+    ) -> Result<(Pool, Option<SshTunnel>), String> {
+        let ssl_mode = config.effective_ssl_mode();
+        let tunnel = Self::open_tunnel(config).await?;
+
         let mut pg_config = Config::new();
         pg_config.host = Some(config.host.clone());
         pg_config.port = Some(config.port);
         pg_config.dbname = Some(config.database.clone());
         pg_config.user = Some(config.username.clone());
         pg_config.password = Some(config.password.clone());
+        pg_config.ssl_mode = Some(tls::pool_ssl_mode(ssl_mode));
+        pg_config.application_name = Some(MONITOR_APPLICATION_NAME.to_string());
+        if let Some(tunnel) = &tunnel {
+            // Keep `host` for TLS hostname checks, connect to the forwarded port
+            pg_config.hostaddr = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+            pg_config.port = Some(tunnel.local_port());
+        }
         pg_config.manager = Some(ManagerConfig {
             recycling_method: RecyclingMethod::Fast,
         });
@@ -91,32 +181,56 @@ impl ConnectionManager {
             ..Default::default()
         });
 
+        let connector = tls::make_tls_connector(config)?;
+
+        let own_backends = self.own_backends.clone();
+        let server_id = config.id.clone();
         let pool = pg_config
-            .create_pool(Some(Runtime::Tokio1), NoTls)
+            .builder(connector)
+            .map_err(|e| format!("Failed to create pool: {}", e))?
+            .runtime(Runtime::Tokio1)
+            .post_create(Hook::async_fn(move |client, _| {
+                let own_backends = own_backends.clone();
+                let server_id = server_id.clone();
+                Box::pin(async move {
+                    let row = client
+                        .query_one(
+                            "SELECT pid, backend_start::text FROM pg_stat_activity WHERE pid = pg_backend_pid()",
+                            &[],
+                        )
+                        .await
+                        .map_err(HookError::Backend)?;
+                    own_backends
+                        .lock()
+                        .unwrap()
+                        .insert((server_id, row.get(0), row.get(1)));
+                    Ok(())
+                })
+            }))
+            .build()
             .map_err(|e| format!("Failed to create pool: {}", e))?;
 
         // Test the connection
         let _client = pool.get()
             .await
-            .map_err(|e| format!("Failed to get connection from pool: {}", e))?;
+            .map_err(|e| format!("Failed to connect: {}", tls::describe_error(ssl_mode, &e.to_string())))?;
+
+        Ok((pool, tunnel))
+    }
+
+    pub async fn add_connection(&self, config: ConnectionConfig) -> Result<(), String> {
+        let (pool, tunnel) = self.create_pool(&config).await?;
 
         // Save to persistent storage
-        let saved_config = SavedServerConfig {
-            id: config.id.clone(),
-            name: config.name.clone(),
-            host: config.host.clone(),
-            port: config.port,
-            database: config.database.clone(),
-            username: config.username.clone(),
-            use_ssl: config.use_ssl,
-            password: config.password.clone(), // Store password for reconnect
-        };
+        let saved_config = SavedServerConfig::from(&config);
         storage::add_server(&saved_config)?;
 
         let mut pools = self.pools.lock().unwrap();
         let mut configs = self.configs.lock().unwrap();
         let mut saved = self.saved_servers.lock().unwrap();
 
+        self.replace_tunnel(&config.id, tunnel);
+        self.capabilities.lock().unwrap().remove(&config.id);
         pools.insert(config.id.clone(), pool);
         configs.insert(config.id.clone(), config);
         saved.insert(saved_config.id.clone(), saved_config);
@@ -135,10 +249,45 @@ impl ConnectionManager {
         pools.remove(id);
         configs.remove(id);
         saved.remove(id);
+        self.replace_tunnel(id, None);
+        self.capabilities.lock().unwrap().remove(id);
+        self.own_backends
+            .lock()
+            .unwrap()
+            .retain(|(server_id, _, _)| server_id != id);
+
+        Ok(())
+    }
+
+    /// Persist a server without connecting to it
+    pub fn save_server(&self, config: &ConnectionConfig) -> Result<(), String> {
+        let saved_config = SavedServerConfig::from(config);
+        storage::add_server(&saved_config)?;
+
+        let mut saved = self.saved_servers.lock().unwrap();
+        saved.insert(saved_config.id.clone(), saved_config);
 
         Ok(())
     }
 
+    /// Whether the backend is a session of one of this server's pools
+    pub fn is_own_backend(&self, id: &str, pid: i32, backend_start: &str) -> bool {
+        let own_backends = self.own_backends.lock().unwrap();
+        own_backends.contains(&(id.to_string(), pid, backend_start.to_string()))
+    }
+
+    /// Ids of servers with an active pool
+    pub fn connected_server_ids(&self) -> Vec<String> {
+        let pools = self.pools.lock().unwrap();
+        pools.keys().cloned().collect()
+    }
+
+    /// Snapshot of all saved server configs
+    pub fn saved_server_configs(&self) -> Vec<SavedServerConfig> {
+        let saved = self.saved_servers.lock().unwrap();
+        saved.values().cloned().collect()
+    }
+
     pub fn get_server_info(&self, id: &str) -> Option<ServerInfo> {
         let configs = self.configs.lock().unwrap();
         let pools = self.pools.lock().unwrap();
@@ -236,53 +385,75 @@ impl ConnectionManager {
             username: server.username,
             password: server.password,
             use_ssl: server.use_ssl,
+            ssl_mode: server.ssl_mode,
+            ssl_root_cert: server.ssl_root_cert,
+            ssl_client_cert: server.ssl_client_cert,
+            ssl_client_key: server.ssl_client_key,
+            ssh_tunnel: server.ssh_tunnel,
         };
 
         // Create pool and connect
-        let mut pg_config = Config::new();
-        pg_config.host = Some(config.host.clone());
-        pg_config.port = Some(config.port);
-        pg_config.dbname = Some(config.database.clone());
-        pg_config.user = Some(config.username.clone());
-        pg_config.password = Some(config.password.clone());
-        pg_config.manager = Some(ManagerConfig {
-            recycling_method: RecyclingMethod::Fast,
-        });
-        pg_config.pool = Some(PoolConfig {
-            max_size: 16,
-            timeouts: Timeouts {
-                wait: Some(Duration::from_secs(30)),
-                create: Some(Duration::from_secs(30)),
-                recycle: Some(Duration::from_secs(10)),
-            },
-            ..Default::default()
-        });
-
-        let pool = pg_config
-            .create_pool(Some(Runtime::Tokio1), NoTls)
-            .map_err(|e| format!("Failed to create pool: {}", e))?;
-
-        // Test the connection
-        let _client = pool.get()
-            .await
-            .map_err(|e| format!("Failed to connect: {}", e))?;
+        let (pool, tunnel) = self.create_pool(&config).await?;
 
         let mut pools = self.pools.lock().unwrap();
         let mut configs = self.configs.lock().unwrap();
 
+        self.replace_tunnel(&config.id, tunnel);
+        self.capabilities.lock().unwrap().remove(&config.id);
         pools.insert(config.id.clone(), pool);
         configs.insert(config.id.clone(), config);
 
         Ok(())
     }
 
+    /// Swap the tunnel of a server; dropping the previous one only signals
+    /// its thread, so this never blocks
+    fn replace_tunnel(&self, id: &str, tunnel: Option<SshTunnel>) {
+        let mut tunnels = self.tunnels.lock().unwrap();
+        match tunnel {
+            Some(tunnel) => tunnels.insert(id.to_string(), tunnel),
+            None => tunnels.remove(id),
+        };
+    }
+
+    /// Version capabilities of a connected server (queried on first use)
+    pub async fn capabilities(&self, id: &str) -> Result<ServerCapabilities, String> {
+        if let Some(capabilities) = self.capabilities.lock().unwrap().get(id) {
+            return Ok(*capabilities);
+        }
+
+        let client = self.get_client(id).await?;
+        let row = client
+            .query_one("SELECT current_setting('server_version_num')::int", &[])
+            .await
+            .map_err(|e| format!("Failed to detect server version: {}", e))?;
+        let capabilities = ServerCapabilities::new(row.get(0));
+
+        self.capabilities
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), capabilities);
+
+        Ok(capabilities)
+    }
+
     pub async fn get_client(&self, id: &str) -> Result<deadpool_postgres::Client, String> {
         let pool = {
             let pools = self.pools.lock().unwrap();
             pools.get(id).cloned().ok_or("Connection not found".to_string())?
         };
 
-        pool.get().await
-            .map_err(|e| format!("Failed to get client: {}", e))
+        let tunnel_down = {
+            let tunnels = self.tunnels.lock().unwrap();
+            tunnels.get(id).is_some_and(|tunnel| !tunnel.is_connected())
+        };
+
+        pool.get().await.map_err(|e| {
+            if tunnel_down {
+                format!("Failed to get client: SSH tunnel is reconnecting ({})", e)
+            } else {
+                format!("Failed to get client: {}", e)
+            }
+        })
     }
 }
@@ -0,0 +1,395 @@
+use super::analyzer::{ConfigAnalyzer, IssueSeverity};
+use super::connection::ConnectionManager;
+use super::queries::MetricsCollector;
+use super::rules::{IssueKind, RuleIssue, RuleRegistry};
+use super::wraparound::XidRateTracker;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+
+/// Issues listed per server, highest penalty first
+const TOP_ISSUES: usize = 5;
+const WRAPAROUND_LIMIT: f64 = 2_147_483_648.0;
+/// Rules measuring what the metric components already score; not run for
+/// the analyzer component so they are not counted twice
+const COMPONENT_RULES: &[&str] = &["cache_hit_ratio", "wraparound", "bloat"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthComponentKind {
+    Analyzer,
+    CacheHitRatio,
+    ConnectionSaturation,
+    ReplicationLag,
+    XidAge,
+    DeadTuples,
+    LongTransactions,
+}
+
+impl HealthComponentKind {
+    fn weight(&self) -> f64 {
+        match self {
+            HealthComponentKind::Analyzer => 0.25,
+            HealthComponentKind::CacheHitRatio => 0.15,
+            HealthComponentKind::ConnectionSaturation => 0.15,
+            HealthComponentKind::ReplicationLag => 0.10,
+            HealthComponentKind::XidAge => 0.15,
+            HealthComponentKind::DeadTuples => 0.10,
+            HealthComponentKind::LongTransactions => 0.10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthComponent {
+    pub component: HealthComponentKind,
+    pub weight: f64,
+    /// 0-100; None when not measured or not applicable (e.g. replication
+    /// lag without replicas), and then left out of the total
+    pub score: Option<f64>,
+    /// Measured value in the unit of the summary
+    pub value: Option<f64>,
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthIssue {
+    pub kind: IssueKind,
+    pub severity: IssueSeverity,
+    pub title: String,
+    pub description: String,
+    /// Points taken off the analyzer component
+    pub penalty: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerHealth {
+    pub server_id: String,
+    pub server_name: Option<String>,
+    /// Weighted average of the measured components, 0-100
+    pub score: Option<f64>,
+    pub components: Vec<HealthComponent>,
+    pub top_issues: Vec<HealthIssue>,
+    /// Set when the server could not be scored at all
+    pub error: Option<String>,
+}
+
+/// Raw values behind the metric components
+struct HealthMetrics {
+    cache_hit_ratio: Option<f64>,
+    connections: i64,
+    max_connections: i64,
+    reserved_connections: i64,
+    max_xid_age: i64,
+    freeze_max_age: i64,
+    dead_tuple_ratio: Option<f64>,
+    longest_transaction_secs: Option<f64>,
+}
+
+/// One health number per server from analyzer results and key metrics
+pub struct HealthScorer;
+
+impl HealthScorer {
+    /// Health of every connected server, worst first
+    pub async fn score_all(
+        manager: &ConnectionManager,
+        rules: &RuleRegistry,
+        xid_tracker: &XidRateTracker,
+    ) -> Vec<ServerHealth> {
+        let mut server_ids = manager.connected_server_ids();
+        server_ids.sort();
+
+        let mut results = join_all(server_ids.into_iter().map(|server_id| async move {
+            match Self::score(manager, &server_id, rules, xid_tracker).await {
+                Ok(health) => health,
+                Err(e) => ServerHealth {
+                    server_name: manager.get_server_info(&server_id).map(|info| info.name),
+                    server_id,
+                    score: None,
+                    components: Vec::new(),
+                    top_issues: Vec::new(),
+                    error: Some(e),
+                },
+            }
+        }))
+        .await;
+
+        results.sort_by(|a, b| match (a.score, b.score) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => a.is_some().cmp(&b.is_some()),
+        });
+        results
+    }
+
+    pub async fn score(
+        manager: &ConnectionManager,
+        server_id: &str,
+        rules: &RuleRegistry,
+        xid_tracker: &XidRateTracker,
+    ) -> Result<ServerHealth, String> {
+        let metrics = Self::collect_metrics(manager, server_id).await?;
+
+        let mut components = vec![
+            Self::cache_component(&metrics),
+            Self::connection_component(&metrics),
+            Self::replication_component(manager, server_id).await,
+            Self::xid_component(&metrics),
+            Self::dead_tuple_component(&metrics),
+            Self::long_transaction_component(&metrics),
+        ];
+
+        let mut issues = Vec::new();
+        let mut analyzer_errors = Vec::new();
+        let rules = rules.without(COMPONENT_RULES);
+        let runs = [
+            ConfigAnalyzer::run_configuration_rules(manager, server_id, &rules).await,
+            ConfigAnalyzer::run_performance_rules(manager, server_id, &rules, xid_tracker).await,
+        ];
+        for run in runs {
+            match run {
+                Ok(run) => {
+                    issues.extend(run.issues.into_iter().map(|(_, issue)| health_issue(issue)))
+                }
+                Err(e) => analyzer_errors.push(e),
+            }
+        }
+
+        let analyzer = if analyzer_errors.len() == 2 {
+            component(
+                HealthComponentKind::Analyzer,
+                None,
+                None,
+                analyzer_errors.join("; "),
+            )
+        } else {
+            let total: f64 = issues.iter().map(|issue| issue.penalty).sum();
+            component(
+                HealthComponentKind::Analyzer,
+                Some((100.0 - total).max(0.0)),
+                Some(issues.len() as f64),
+                format!("{} analyzer issues", issues.len()),
+            )
+        };
+        components.insert(0, analyzer);
+
+        issues.sort_by(|a, b| b.penalty.total_cmp(&a.penalty));
+        issues.truncate(TOP_ISSUES);
+
+        let measured: Vec<(f64, f64)> = components
+            .iter()
+            .filter_map(|c| c.score.map(|score| (score, c.weight)))
+            .collect();
+        let weight: f64 = measured.iter().map(|(_, weight)| weight).sum();
+        let score = (weight > 0.0).then(|| {
+            measured
+                .iter()
+                .map(|(score, weight)| score * weight)
+                .sum::<f64>()
+                / weight
+        });
+
+        Ok(ServerHealth {
+            server_id: server_id.to_string(),
+            server_name: manager.get_server_info(server_id).map(|info| info.name),
+            score,
+            components,
+            top_issues: issues,
+            error: None,
+        })
+    }
+
+    async fn collect_metrics(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<HealthMetrics, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let query = r#"
+            SELECT
+                (SELECT sum(blks_hit)::float8 / NULLIF(sum(blks_hit + blks_read), 0) * 100
+                 FROM pg_stat_database),
+                (SELECT count(*) FROM pg_stat_activity WHERE datname IS NOT NULL),
+                current_setting('max_connections')::bigint,
+                current_setting('superuser_reserved_connections')::bigint,
+                (SELECT max(age(datfrozenxid))::bigint FROM pg_database),
+                current_setting('autovacuum_freeze_max_age')::bigint,
+                (SELECT sum(n_dead_tup)::float8 / NULLIF(sum(n_live_tup + n_dead_tup), 0) * 100
+                 FROM pg_stat_user_tables),
+                (SELECT max(EXTRACT(EPOCH FROM now() - xact_start))::float8
+                 FROM pg_stat_activity
+                 WHERE xact_start IS NOT NULL AND pid <> pg_backend_pid())
+        "#;
+
+        let row = client
+            .query_one(query, &[])
+            .await
+            .map_err(|e| format!("Failed to query health metrics: {}", e))?;
+
+        Ok(HealthMetrics {
+            cache_hit_ratio: row.get(0),
+            connections: row.get(1),
+            max_connections: row.get(2),
+            reserved_connections: row.get(3),
+            max_xid_age: row.get::<_, Option<i64>>(4).unwrap_or(0),
+            freeze_max_age: row.get(5),
+            dead_tuple_ratio: row.get(6),
+            longest_transaction_secs: row.get(7),
+        })
+    }
+
+    fn cache_component(metrics: &HealthMetrics) -> HealthComponent {
+        let ratio = metrics.cache_hit_ratio;
+        component(
+            HealthComponentKind::CacheHitRatio,
+            ratio.map(|r| linear(r, 99.0, 80.0)),
+            ratio,
+            match ratio {
+                Some(r) => format!("{:.2}% of blocks read from shared buffers", r),
+                None => "No block reads yet".to_string(),
+            },
+        )
+    }
+
+    fn connection_component(metrics: &HealthMetrics) -> HealthComponent {
+        let available = (metrics.max_connections - metrics.reserved_connections).max(1);
+        let used = metrics.connections as f64 / available as f64 * 100.0;
+        component(
+            HealthComponentKind::ConnectionSaturation,
+            Some(linear(used, 70.0, 95.0)),
+            Some(used),
+            format!(
+                "{} of {} connections in use ({:.0}%)",
+                metrics.connections, available, used
+            ),
+        )
+    }
+
+    /// Worst replica replay lag on a primary, own replay lag on a standby
+    async fn replication_component(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> HealthComponent {
+        let lag = match MetricsCollector::get_recovery_status(manager, server_id).await {
+            Ok(status) if status.in_recovery => Ok(status.replay_lag_secs),
+            Ok(_) => MetricsCollector::get_replication_stats(manager, server_id)
+                .await
+                .map(|replicas| {
+                    replicas
+                        .iter()
+                        .map(|r| r.replay_lag_secs.unwrap_or(0.0))
+                        .reduce(f64::max)
+                }),
+            Err(e) => Err(e),
+        };
+
+        match lag {
+            Ok(Some(secs)) => component(
+                HealthComponentKind::ReplicationLag,
+                Some(linear(secs, 10.0, 300.0)),
+                Some(secs),
+                format!("Replay lag {:.1}s", secs),
+            ),
+            Ok(None) => component(
+                HealthComponentKind::ReplicationLag,
+                None,
+                None,
+                "No replication".to_string(),
+            ),
+            Err(e) => component(HealthComponentKind::ReplicationLag, None, None, e),
+        }
+    }
+
+    /// Full marks up to autovacuum_freeze_max_age, none halfway from there
+    /// to wraparound
+    fn xid_component(metrics: &HealthMetrics) -> HealthComponent {
+        let age = metrics.max_xid_age as f64;
+        let freeze_max_age = metrics.freeze_max_age as f64;
+        component(
+            HealthComponentKind::XidAge,
+            Some(linear(
+                age,
+                freeze_max_age,
+                (freeze_max_age + WRAPAROUND_LIMIT) / 2.0,
+            )),
+            Some(age),
+            format!(
+                "Oldest database XID age {} ({:.1}% of wraparound)",
+                metrics.max_xid_age,
+                age / WRAPAROUND_LIMIT * 100.0
+            ),
+        )
+    }
+
+    fn dead_tuple_component(metrics: &HealthMetrics) -> HealthComponent {
+        let ratio = metrics.dead_tuple_ratio;
+        component(
+            HealthComponentKind::DeadTuples,
+            ratio.map(|r| linear(r, 5.0, 30.0)),
+            ratio,
+            match ratio {
+                Some(r) => format!("{:.1}% of tuples are dead", r),
+                None => "No user tables".to_string(),
+            },
+        )
+    }
+
+    fn long_transaction_component(metrics: &HealthMetrics) -> HealthComponent {
+        let secs = metrics.longest_transaction_secs.unwrap_or(0.0);
+        component(
+            HealthComponentKind::LongTransactions,
+            Some(linear(secs, 60.0, 3600.0)),
+            Some(secs),
+            match metrics.longest_transaction_secs {
+                Some(secs) => format!("Oldest open transaction started {:.0}s ago", secs),
+                None => "No open transactions".to_string(),
+            },
+        )
+    }
+}
+
+fn component(
+    kind: HealthComponentKind,
+    score: Option<f64>,
+    value: Option<f64>,
+    summary: String,
+) -> HealthComponent {
+    HealthComponent {
+        component: kind,
+        weight: kind.weight(),
+        score,
+        value,
+        summary,
+    }
+}
+
+/// 100 at `good`, 0 at `bad`, linear in between; `good` may be above or
+/// below `bad`
+fn linear(value: f64, good: f64, bad: f64) -> f64 {
+    ((value - bad) / (good - bad) * 100.0).clamp(0.0, 100.0)
+}
+
+fn health_issue(issue: RuleIssue) -> HealthIssue {
+    match issue {
+        RuleIssue::Config(issue) => HealthIssue {
+            kind: IssueKind::Config,
+            penalty: penalty(&issue.severity),
+            severity: issue.severity,
+            title: issue.parameter,
+            description: issue.reason,
+        },
+        RuleIssue::Performance(issue) => HealthIssue {
+            kind: IssueKind::Performance,
+            penalty: penalty(&issue.severity),
+            severity: issue.severity,
+            title: issue.issue_type,
+            description: issue.description,
+        },
+    }
+}
+
+fn penalty(severity: &IssueSeverity) -> f64 {
+    match severity {
+        IssueSeverity::Critical => 25.0,
+        IssueSeverity::Warning => 10.0,
+        IssueSeverity::Info => 2.0,
+    }
+}
@@ -0,0 +1,346 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Metric families persisted by the sampler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryMetric {
+    DatabaseStats,
+    BgWriterStats,
+    TableStats,
+    DatabaseSize,
+}
+
+impl HistoryMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryMetric::DatabaseStats => "database_stats",
+            HistoryMetric::BgWriterStats => "bgwriter_stats",
+            HistoryMetric::TableStats => "table_stats",
+            HistoryMetric::DatabaseSize => "database_size",
+        }
+    }
+}
+
+/// Storage resolution of a sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resolution {
+    #[serde(rename = "raw")]
+    Raw,
+    #[serde(rename = "1m")]
+    Minute,
+    #[serde(rename = "1h")]
+    Hour,
+}
+
+impl Resolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::Minute => "1m",
+            Resolution::Hour => "1h",
+        }
+    }
+}
+
+/// One numeric field of a snapshot, e.g. `xact_commit` of database `app`
+#[derive(Debug, Clone)]
+pub struct SamplePoint {
+    /// Database, `schema.table`, or empty for cluster-wide metrics
+    pub object: String,
+    pub field: String,
+    pub value: f64,
+    /// Cumulative counters downsample to the last value, gauges to the mean
+    pub counter: bool,
+}
+
+/// Time range request for one metric of one server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryQuery {
+    pub server_id: String,
+    pub metric: HistoryMetric,
+    /// Unix timestamps in seconds, inclusive
+    pub from: i64,
+    pub to: i64,
+    #[serde(default)]
+    pub object: Option<String>,
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default)]
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPoint {
+    /// Unix timestamp in seconds
+    pub ts: i64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricSeries {
+    pub object: String,
+    pub field: String,
+    pub points: Vec<HistoryPoint>,
+}
+
+/// How long each resolution is kept before being rolled up or dropped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub raw_hours: i64,
+    pub minute_days: i64,
+    pub hour_days: i64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            raw_hours: 6,
+            minute_days: 7,
+            hour_days: 90,
+        }
+    }
+}
+
+/// Embedded SQLite time-series store for sampled metrics
+pub struct MetricsStore {
+    conn: Mutex<Connection>,
+}
+
+impl MetricsStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open metrics store: {}", e))?;
+
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            r#"
+            PRAGMA journal_mode = WAL;
+            PRAGMA synchronous = NORMAL;
+            CREATE TABLE IF NOT EXISTS samples (
+                server_id  TEXT    NOT NULL,
+                metric     TEXT    NOT NULL,
+                object     TEXT    NOT NULL,
+                field      TEXT    NOT NULL,
+                resolution TEXT    NOT NULL,
+                ts         INTEGER NOT NULL,
+                value      REAL    NOT NULL,
+                counter    INTEGER NOT NULL,
+                PRIMARY KEY (server_id, metric, object, field, resolution, ts)
+            ) WITHOUT ROWID;
+            CREATE INDEX IF NOT EXISTS samples_by_time
+                ON samples (server_id, metric, ts);
+            "#,
+        )
+        .map_err(|e| format!("Failed to initialize metrics store: {}", e))?;
+
+        Ok(MetricsStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Non-persistent store used when the data directory is unavailable
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open metrics store: {}", e))?;
+        Self::init(conn)
+    }
+
+    pub fn record(
+        &self,
+        server_id: &str,
+        metric: HistoryMetric,
+        ts: i64,
+        points: &[SamplePoint],
+    ) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        {
+            let mut stmt = tx
+                .prepare_cached(
+                    r#"
+                    INSERT OR REPLACE INTO samples
+                        (server_id, metric, object, field, resolution, ts, value, counter)
+                    VALUES (?1, ?2, ?3, ?4, 'raw', ?5, ?6, ?7)
+                    "#,
+                )
+                .map_err(|e| format!("Failed to prepare insert: {}", e))?;
+
+            for point in points {
+                stmt.execute(params![
+                    server_id,
+                    metric.as_str(),
+                    point.object,
+                    point.field,
+                    ts,
+                    point.value,
+                    point.counter,
+                ])
+                .map_err(|e| format!("Failed to store sample: {}", e))?;
+            }
+        }
+
+        tx.commit().map_err(|e| format!("Failed to commit samples: {}", e))
+    }
+
+    /// Return series matching a history query. Without a resolution, all
+    /// tiers are merged (they cover disjoint time ranges after downsampling).
+    pub fn query(&self, query: &HistoryQuery) -> Result<Vec<MetricSeries>, String> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare_cached(
+                r#"
+                SELECT object, field, ts, value
+                FROM samples
+                WHERE server_id = ?1
+                  AND metric = ?2
+                  AND ts BETWEEN ?3 AND ?4
+                  AND (?5 IS NULL OR object = ?5)
+                  AND (?6 IS NULL OR field = ?6)
+                  AND (?7 IS NULL OR resolution = ?7)
+                ORDER BY object, field, ts
+                "#,
+            )
+            .map_err(|e| format!("Failed to prepare history query: {}", e))?;
+
+        let rows = stmt
+            .query_map(
+                params![
+                    query.server_id,
+                    query.metric.as_str(),
+                    query.from,
+                    query.to,
+                    query.object,
+                    query.field,
+                    query.resolution.map(|r| r.as_str()),
+                ],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, f64>(3)?,
+                    ))
+                },
+            )
+            .map_err(|e| format!("Failed to query history: {}", e))?;
+
+        let mut series: Vec<MetricSeries> = Vec::new();
+        for row in rows {
+            let (object, field, ts, value) = row.map_err(|e| format!("Failed to read history: {}", e))?;
+            match series.last_mut() {
+                Some(last) if last.object == object && last.field == field => {
+                    last.points.push(HistoryPoint { ts, value });
+                }
+                _ => series.push(MetricSeries {
+                    object,
+                    field,
+                    points: vec![HistoryPoint { ts, value }],
+                }),
+            }
+        }
+
+        Ok(series)
+    }
+
+    /// Roll raw samples up to 1-minute buckets and 1-minute buckets up to
+    /// 1-hour buckets once they age out, then drop expired hourly data
+    pub fn compact(&self, retention: &RetentionPolicy, now: i64) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        // Align cutoffs to bucket boundaries so only complete buckets are rolled up
+        let raw_cutoff = (now - retention.raw_hours * 3600) / 60 * 60;
+        let minute_cutoff = (now - retention.minute_days * 86400) / 3600 * 3600;
+        let hour_cutoff = now - retention.hour_days * 86400;
+
+        Self::rollup(&tx, Resolution::Raw, Resolution::Minute, 60, raw_cutoff)?;
+        Self::rollup(&tx, Resolution::Minute, Resolution::Hour, 3600, minute_cutoff)?;
+
+        tx.execute(
+            "DELETE FROM samples WHERE resolution = ?1 AND ts < ?2",
+            params![Resolution::Hour.as_str(), hour_cutoff],
+        )
+        .map_err(|e| format!("Failed to expire history: {}", e))?;
+
+        tx.commit().map_err(|e| format!("Failed to commit compaction: {}", e))
+    }
+
+    fn rollup(
+        tx: &rusqlite::Transaction<'_>,
+        from: Resolution,
+        to: Resolution,
+        bucket_secs: i64,
+        cutoff: i64,
+    ) -> Result<(), String> {
+        tx.execute(
+            r#"
+            INSERT OR REPLACE INTO samples
+                (server_id, metric, object, field, resolution, ts, value, counter)
+            SELECT
+                server_id, metric, object, field, ?2, bucket,
+                CASE WHEN counter THEN (
+                    -- Counters may reset within a bucket, so take the last
+                    -- value rather than the largest
+                    SELECT s.value FROM samples s
+                    WHERE s.server_id = g.server_id AND s.metric = g.metric
+                        AND s.object = g.object AND s.field = g.field
+                        AND s.resolution = ?1 AND s.ts = g.last_ts
+                ) ELSE avg_value END,
+                counter
+            FROM (
+                SELECT
+                    server_id, metric, object, field, counter,
+                    (ts / ?3) * ?3 AS bucket,
+                    AVG(value) AS avg_value,
+                    MAX(ts) AS last_ts
+                FROM samples
+                WHERE resolution = ?1 AND ts < ?4
+                GROUP BY server_id, metric, object, field, bucket, counter
+            ) AS g
+            "#,
+            params![from.as_str(), to.as_str(), bucket_secs, cutoff],
+        )
+        .map_err(|e| format!("Failed to downsample {} samples: {}", from.as_str(), e))?;
+
+        tx.execute(
+            "DELETE FROM samples WHERE resolution = ?1 AND ts < ?2",
+            params![from.as_str(), cutoff],
+        )
+        .map_err(|e| format!("Failed to delete {} samples: {}", from.as_str(), e))?;
+
+        Ok(())
+    }
+
+    pub fn delete_server(&self, server_id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM samples WHERE server_id = ?1", params![server_id])
+            .map_err(|e| format!("Failed to delete history: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Current time as a unix timestamp in seconds
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
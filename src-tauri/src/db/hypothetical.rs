@@ -0,0 +1,368 @@
+use super::connection::ConnectionManager;
+use super::metrics::{ExplainNode, ExplainPlan};
+use super::queries::MetricsCollector;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Widest composite candidate built from a scan's filter columns
+const MAX_COMPOSITE_COLUMNS: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexSuggestion {
+    pub schemaname: String,
+    pub table_name: String,
+    pub columns: Vec<String>,
+    /// Statement to create the index for real
+    pub ddl: String,
+    /// Why the columns were picked, e.g. "Filter" or "Join condition"
+    pub source: String,
+    pub total_cost: f64,
+    /// Percentage of the baseline cost saved; negative when worse
+    pub cost_reduction_pct: f64,
+    /// The planner chose the hypothetical index
+    pub used_by_plan: bool,
+    /// Estimated size from hypopg_relation_size
+    pub estimated_size_bytes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexSuggestions {
+    pub query: String,
+    pub baseline_cost: f64,
+    /// Best first
+    pub suggestions: Vec<IndexSuggestion>,
+}
+
+/// Candidate index on one scanned relation
+#[derive(Debug, Clone)]
+struct Candidate {
+    schemaname: String,
+    table_name: String,
+    /// Deparsed column names as printed by EXPLAIN, already quoted
+    columns: Vec<String>,
+    source: &'static str,
+}
+
+/// Proposes indexes for the sequential scans of a plan and measures them
+/// with hypopg; real indexes are never created
+pub struct IndexSuggester;
+
+impl IndexSuggester {
+    pub async fn suggest(
+        manager: &ConnectionManager,
+        server_id: &str,
+        query: &str,
+    ) -> Result<IndexSuggestions, String> {
+        MetricsCollector::check_explainable(query)?;
+        let client = manager.get_client(server_id).await?;
+
+        let installed = client
+            .query_opt("SELECT 1 FROM pg_extension WHERE extname = 'hypopg'", &[])
+            .await
+            .map_err(|e| format!("Failed to check hypopg: {}", e))?
+            .is_some();
+        if !installed {
+            return Err("The hypopg extension is not installed in this database".to_string());
+        }
+
+        // Hypothetical indexes only exist in this session; start clean
+        client
+            .execute("SELECT hypopg_reset()", &[])
+            .await
+            .map_err(|e| format!("Failed to reset hypothetical indexes: {}", e))?;
+
+        let baseline = Self::explain(&client, query).await?;
+        let candidates = Self::candidates(&baseline.root);
+
+        let mut suggestions = Vec::new();
+        let mut result = Ok(());
+        for candidate in candidates {
+            match Self::measure(&client, query, &candidate, baseline.total_cost).await {
+                Ok(suggestion) => suggestions.push(suggestion),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let reset = client
+            .execute("SELECT hypopg_reset()", &[])
+            .await
+            .map_err(|e| format!("Failed to reset hypothetical indexes: {}", e));
+        result.and(reset)?;
+
+        suggestions.sort_by(|a, b| {
+            b.used_by_plan
+                .cmp(&a.used_by_plan)
+                .then(b.cost_reduction_pct.total_cmp(&a.cost_reduction_pct))
+        });
+
+        Ok(IndexSuggestions {
+            query: query.to_string(),
+            baseline_cost: baseline.total_cost,
+            suggestions,
+        })
+    }
+
+    async fn explain(
+        client: &deadpool_postgres::Client,
+        query: &str,
+    ) -> Result<ExplainPlan, String> {
+        // hypopg indexes are ignored by EXPLAIN ANALYZE
+        let row = client
+            .query_one(
+                &format!("EXPLAIN (COSTS, VERBOSE, FORMAT JSON) {}", query),
+                &[],
+            )
+            .await
+            .map_err(|e| format!("Failed to explain query: {}", e))?;
+
+        let json_plan: Value = row.get(0);
+        MetricsCollector::parse_explain_output(query, &json_plan)
+    }
+
+    /// Create one hypothetical index, re-plan, and drop it again
+    async fn measure(
+        client: &deadpool_postgres::Client,
+        query: &str,
+        candidate: &Candidate,
+        baseline_cost: f64,
+    ) -> Result<IndexSuggestion, String> {
+        let row = client
+            .query_one(
+                r#"
+                SELECT h.indexrelid, h.indexname, d.ddl
+                FROM (SELECT format('CREATE INDEX ON %I.%I (%s)', $1::text, $2::text, $3::text) AS ddl) d,
+                    hypopg_create_index(d.ddl) h
+                "#,
+                &[
+                    &candidate.schemaname,
+                    &candidate.table_name,
+                    &candidate.columns.join(", "),
+                ],
+            )
+            .await
+            .map_err(|e| format!("Failed to create hypothetical index: {}", e))?;
+
+        let indexrelid: u32 = row.get(0);
+        let indexname: String = row.get(1);
+        let ddl: String = row.get(2);
+
+        let estimated_size_bytes = client
+            .query_one("SELECT hypopg_relation_size($1)", &[&indexrelid])
+            .await
+            .ok()
+            .map(|row| row.get(0));
+
+        let plan = Self::explain(client, query).await;
+
+        client
+            .execute("SELECT hypopg_reset()", &[])
+            .await
+            .map_err(|e| format!("Failed to reset hypothetical indexes: {}", e))?;
+
+        let plan = plan?;
+        Ok(IndexSuggestion {
+            schemaname: candidate.schemaname.clone(),
+            table_name: candidate.table_name.clone(),
+            columns: candidate.columns.clone(),
+            ddl: format!(
+                "{};",
+                ddl.replacen("CREATE INDEX", "CREATE INDEX CONCURRENTLY", 1)
+            ),
+            source: candidate.source.to_string(),
+            total_cost: plan.total_cost,
+            cost_reduction_pct: if baseline_cost > 0.0 {
+                (baseline_cost - plan.total_cost) / baseline_cost * 100.0
+            } else {
+                0.0
+            },
+            used_by_plan: uses_index(&plan.root, &indexname),
+            estimated_size_bytes,
+        })
+    }
+
+    /// Filter and join columns of every sequential scan in the plan
+    fn candidates(root: &ExplainNode) -> Vec<Candidate> {
+        let mut scans = Vec::new();
+        collect_scans(root, &mut scans);
+
+        let mut join_conditions = Vec::new();
+        collect_join_conditions(root, &mut join_conditions);
+        let join_refs: Vec<(String, String)> = join_conditions
+            .iter()
+            .flat_map(|cond| column_refs(cond))
+            .collect();
+
+        let mut candidates: Vec<Candidate> = Vec::new();
+        let mut push = |candidate: Candidate| {
+            if !candidate.columns.is_empty()
+                && !candidates.iter().any(|c| {
+                    c.schemaname == candidate.schemaname
+                        && c.table_name == candidate.table_name
+                        && c.columns == candidate.columns
+                })
+            {
+                candidates.push(candidate);
+            }
+        };
+
+        for scan in scans {
+            let (Some(schemaname), Some(table_name)) = (&scan.schema, &scan.relation_name) else {
+                continue;
+            };
+            let alias = scan.alias.as_deref().unwrap_or(table_name);
+            let columns_of = |refs: &[(String, String)]| {
+                let mut columns: Vec<String> = Vec::new();
+                for (qualifier, column) in refs {
+                    if qualifier == alias && !columns.contains(column) {
+                        columns.push(column.clone());
+                    }
+                }
+                columns
+            };
+
+            let candidate = |columns: Vec<String>, source| Candidate {
+                schemaname: schemaname.clone(),
+                table_name: table_name.clone(),
+                columns,
+                source,
+            };
+
+            let filter_columns =
+                columns_of(&scan.filter.as_deref().map(column_refs).unwrap_or_default());
+            for column in &filter_columns {
+                push(candidate(vec![column.clone()], "Filter"));
+            }
+            if filter_columns.len() > 1 {
+                let composite = filter_columns
+                    .iter()
+                    .take(MAX_COMPOSITE_COLUMNS)
+                    .cloned()
+                    .collect();
+                push(candidate(composite, "Filter"));
+            }
+
+            for column in columns_of(&join_refs) {
+                push(candidate(vec![column], "Join condition"));
+            }
+        }
+
+        candidates
+    }
+}
+
+fn collect_scans<'a>(node: &'a ExplainNode, scans: &mut Vec<&'a ExplainNode>) {
+    if node.node_type == "Seq Scan" {
+        scans.push(node);
+    }
+    for child in &node.children {
+        collect_scans(child, scans);
+    }
+}
+
+fn collect_join_conditions(node: &ExplainNode, conditions: &mut Vec<String>) {
+    if let Some(cond) = &node.hash_cond {
+        conditions.push(cond.clone());
+    }
+    // Nested loop join clauses end up as the inner scan's filter
+    if node.node_type == "Nested Loop" {
+        for child in &node.children {
+            if let Some(filter) = &child.filter {
+                conditions.push(filter.clone());
+            }
+        }
+    }
+    for child in &node.children {
+        collect_join_conditions(child, conditions);
+    }
+}
+
+fn uses_index(node: &ExplainNode, index_name: &str) -> bool {
+    node.index_name.as_deref() == Some(index_name)
+        || node
+            .children
+            .iter()
+            .any(|child| uses_index(child, index_name))
+}
+
+/// Qualified column references (`alias.column`) in a deparsed EXPLAIN
+/// expression, as (unquoted qualifier, column as printed). Function names
+/// and string literals are skipped.
+fn column_refs(expr: &str) -> Vec<(String, String)> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut refs = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' {
+            i = skip_quoted(&chars, i, '\'');
+        } else if c == '"' || c.is_alphabetic() || c == '_' {
+            // A name right after "::" is a type, not a column
+            let is_type = i >= 2 && chars[i - 1] == ':' && chars[i - 2] == ':';
+            let mut chain = Vec::new();
+            loop {
+                let start = i;
+                i = if chars[i] == '"' {
+                    skip_quoted(&chars, i, '"')
+                } else {
+                    skip_word(&chars, i)
+                };
+                chain.push(chars[start..i].iter().collect::<String>());
+                if i + 1 < chars.len()
+                    && chars[i] == '.'
+                    && (chars[i + 1] == '"' || chars[i + 1].is_alphabetic() || chars[i + 1] == '_')
+                {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+
+            let is_call = chars.get(i) == Some(&'(');
+            if !is_type && !is_call && chain.len() >= 2 {
+                let column = chain.pop().unwrap_or_default();
+                let qualifier = chain.pop().unwrap_or_default();
+                refs.push((unquote(&qualifier), column));
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    refs
+}
+
+/// Index just past the closing quote starting at `start`; doubled quotes
+/// are escapes
+fn skip_quoted(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+fn skip_word(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+        i += 1;
+    }
+    i
+}
+
+fn unquote(ident: &str) -> String {
+    match ident.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => ident.to_string(),
+    }
+}
@@ -0,0 +1,567 @@
+use super::analyzer::{IssueSeverity, PerformanceIssue};
+use super::connection::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Unused indexes smaller than this are not reported
+const MIN_UNUSED_BYTES: i64 = 1024 * 1024;
+/// Findings reclaiming more than this are warnings rather than info
+const WARNING_BYTES: i64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexFindingKind {
+    /// Same table, access method, columns, operator classes and predicate
+    /// as another index
+    Duplicate,
+    /// Key columns are a leading prefix of another btree index
+    Redundant,
+    /// Left behind by a failed CREATE INDEX CONCURRENTLY or REINDEX
+    Invalid,
+    /// Foreign key whose columns do not lead any index on the table
+    MissingFkIndex,
+    /// Never scanned on this server or its connected replicas
+    Unused,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexFinding {
+    pub kind: IndexFindingKind,
+    pub schemaname: String,
+    pub table_name: String,
+    /// Index at fault; None for missing foreign key indexes
+    pub index_name: Option<String>,
+    pub definition: Option<String>,
+    /// Index that makes this one unnecessary (duplicate and redundant)
+    pub covered_by: Option<String>,
+    /// Foreign key constraint (missing_fk_index)
+    pub constraint_name: Option<String>,
+    /// Scans summed over this server and its replicas
+    pub idx_scan: Option<i64>,
+    /// Space freed by applying fix_ddl; 0 when it adds an index
+    pub reclaimable_bytes: i64,
+    pub fix_ddl: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexReport {
+    pub findings: Vec<IndexFinding>,
+    pub reclaimable_bytes: i64,
+    /// Connected servers of the same cluster and database whose index
+    /// usage was added to this server's
+    pub replicas_checked: Vec<String>,
+    /// Last statistics reset of the database on this server; usage before
+    /// it is not counted
+    pub stats_reset: Option<String>,
+}
+
+/// Catalog entry of one index in the connected database
+struct IndexEntry {
+    oid: u32,
+    table_oid: u32,
+    schemaname: String,
+    table_name: String,
+    index_name: String,
+    /// Schema-qualified and quoted, for DDL
+    qualified_name: String,
+    definition: String,
+    amname: String,
+    /// Key columns (attnums, 0 for expressions), without INCLUDE columns
+    key_columns: Vec<i16>,
+    /// Key plus INCLUDE columns
+    all_columns: Vec<i16>,
+    opclasses: Vec<String>,
+    options: Vec<String>,
+    expressions: Option<String>,
+    predicate: Option<String>,
+    is_unique: bool,
+    is_primary: bool,
+    is_valid: bool,
+    /// Backs a primary key, unique or exclusion constraint
+    is_constraint: bool,
+    size_bytes: i64,
+    idx_scan: i64,
+}
+
+impl IndexEntry {
+    /// Must be kept when another index looks the same
+    fn is_required(&self) -> bool {
+        self.is_primary || self.is_constraint
+    }
+
+    fn is_plain(&self) -> bool {
+        self.is_valid && self.expressions.is_none() && self.predicate.is_none()
+    }
+}
+
+pub struct IndexAdvisor;
+
+impl IndexAdvisor {
+    pub async fn get_report(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<IndexReport, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let client = manager.get_client(server_id).await?;
+
+        // indnkeyatts separates INCLUDE columns since PG 11
+        let nkeyatts = if capabilities.version_num >= 110000 {
+            "i.indnkeyatts"
+        } else {
+            "i.indnatts"
+        };
+
+        let query = format!(
+            r#"
+            SELECT
+                i.indexrelid,
+                i.indrelid,
+                n.nspname::text,
+                t.relname::text,
+                ic.relname::text,
+                quote_ident(n.nspname) || '.' || quote_ident(ic.relname),
+                pg_get_indexdef(i.indexrelid),
+                am.amname::text,
+                i.indkey::text,
+                {nkeyatts}::int,
+                i.indclass::text,
+                i.indoption::text,
+                pg_get_expr(i.indexprs, i.indrelid),
+                pg_get_expr(i.indpred, i.indrelid),
+                i.indisunique,
+                i.indisprimary,
+                i.indisvalid,
+                EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conindid = i.indexrelid
+                        AND c.contype IN ('p', 'u', 'x')),
+                pg_relation_size(i.indexrelid),
+                COALESCE(s.idx_scan, 0)
+            FROM pg_index i
+            JOIN pg_class ic ON ic.oid = i.indexrelid
+            JOIN pg_class t ON t.oid = i.indrelid
+            JOIN pg_namespace n ON n.oid = t.relnamespace
+            JOIN pg_am am ON am.oid = ic.relam
+            LEFT JOIN pg_stat_all_indexes s ON s.indexrelid = i.indexrelid
+            WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+                AND n.nspname !~ '^pg_toast'
+            ORDER BY n.nspname, t.relname, ic.relname
+            "#
+        );
+
+        let rows = client
+            .query(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to query indexes: {}", e))?;
+
+        let mut indexes: Vec<IndexEntry> = rows
+            .iter()
+            .map(|row| {
+                let all_columns = parse_vector::<i16>(row.get(8));
+                let nkeyatts = (row.get::<_, i32>(9).max(0) as usize).min(all_columns.len());
+                IndexEntry {
+                    oid: row.get(0),
+                    table_oid: row.get(1),
+                    schemaname: row.get(2),
+                    table_name: row.get(3),
+                    index_name: row.get(4),
+                    qualified_name: row.get(5),
+                    definition: row.get(6),
+                    amname: row.get(7),
+                    key_columns: all_columns[..nkeyatts].to_vec(),
+                    all_columns,
+                    opclasses: parse_vector::<String>(row.get(10)),
+                    options: parse_vector::<String>(row.get(11)),
+                    expressions: row.get(12),
+                    predicate: row.get(13),
+                    is_unique: row.get(14),
+                    is_primary: row.get(15),
+                    is_valid: row.get(16),
+                    is_constraint: row.get(17),
+                    size_bytes: row.get(18),
+                    idx_scan: row.get(19),
+                }
+            })
+            .collect();
+
+        let (replicas_checked, replica_scans) =
+            Self::replica_usage(manager, server_id, &client).await;
+        for index in &mut indexes {
+            index.idx_scan += replica_scans.get(&index.oid).copied().unwrap_or(0);
+        }
+
+        let mut findings = Vec::new();
+        let mut flagged: HashSet<u32> = HashSet::new();
+
+        for index in indexes.iter().filter(|i| !i.is_valid) {
+            flagged.insert(index.oid);
+            let fix_ddl = if capabilities.version_num >= 120000 {
+                format!(
+                    "REINDEX INDEX CONCURRENTLY {};\n-- or, if it is not needed:\nDROP INDEX CONCURRENTLY {};",
+                    index.qualified_name, index.qualified_name
+                )
+            } else {
+                format!(
+                    "DROP INDEX CONCURRENTLY {};\n{};",
+                    index.qualified_name,
+                    index.definition.replacen("INDEX", "INDEX CONCURRENTLY", 1)
+                )
+            };
+            findings.push(Self::index_finding(
+                IndexFindingKind::Invalid,
+                index,
+                None,
+                fix_ddl,
+                "Invalid index is maintained on every write but never used by the planner"
+                    .to_string(),
+            ));
+        }
+
+        Self::duplicates(&indexes, &mut flagged, &mut findings);
+        Self::redundant(&indexes, &mut flagged, &mut findings);
+
+        for index in &indexes {
+            if flagged.contains(&index.oid)
+                || index.idx_scan > 0
+                || index.is_unique
+                || index.is_required()
+                || index.size_bytes < MIN_UNUSED_BYTES
+            {
+                continue;
+            }
+            findings.push(Self::index_finding(
+                IndexFindingKind::Unused,
+                index,
+                None,
+                format!("DROP INDEX CONCURRENTLY {};", index.qualified_name),
+                "Index has not been scanned since statistics were last reset".to_string(),
+            ));
+        }
+
+        findings.extend(Self::missing_fk_indexes(&client, &indexes).await?);
+
+        let row = client
+            .query_one(
+                "SELECT min(stats_reset)::text FROM pg_stat_database WHERE datname = current_database()",
+                &[],
+            )
+            .await
+            .map_err(|e| format!("Failed to query stats reset: {}", e))?;
+
+        Ok(IndexReport {
+            reclaimable_bytes: findings.iter().map(|f| f.reclaimable_bytes).sum(),
+            findings,
+            replicas_checked,
+            stats_reset: row.get(0),
+        })
+    }
+
+    /// Indexes identical to another one; the constraint-backing or oldest
+    /// index of each group is kept
+    fn duplicates(
+        indexes: &[IndexEntry],
+        flagged: &mut HashSet<u32>,
+        findings: &mut Vec<IndexFinding>,
+    ) {
+        let mut groups: HashMap<_, Vec<&IndexEntry>> = HashMap::new();
+        for index in indexes.iter().filter(|i| i.is_valid) {
+            let key = (
+                index.table_oid,
+                &index.amname,
+                &index.all_columns,
+                &index.opclasses,
+                &index.options,
+                &index.expressions,
+                &index.predicate,
+            );
+            groups.entry(key).or_default().push(index);
+        }
+
+        for mut group in groups.into_values().filter(|g| g.len() > 1) {
+            group.sort_by_key(|i| (!i.is_required(), !i.is_unique, i.oid));
+            let keep = group[0];
+            for index in &group[1..] {
+                if index.is_required() {
+                    continue;
+                }
+                flagged.insert(index.oid);
+                findings.push(Self::index_finding(
+                    IndexFindingKind::Duplicate,
+                    index,
+                    Some(keep),
+                    format!("DROP INDEX CONCURRENTLY {};", index.qualified_name),
+                    format!("Exact duplicate of {}", keep.index_name),
+                ));
+            }
+        }
+    }
+
+    /// Non-unique btree indexes whose key columns lead another btree index
+    /// with the same operator classes
+    fn redundant(
+        indexes: &[IndexEntry],
+        flagged: &mut HashSet<u32>,
+        findings: &mut Vec<IndexFinding>,
+    ) {
+        let btree = |i: &&IndexEntry| i.amname == "btree" && i.is_plain();
+
+        for index in indexes.iter().filter(btree) {
+            if flagged.contains(&index.oid) || index.is_unique || index.is_required() {
+                continue;
+            }
+            let n = index.key_columns.len();
+            let covering = indexes.iter().filter(btree).find(|other| {
+                other.oid != index.oid
+                    && other.table_oid == index.table_oid
+                    && !flagged.contains(&other.oid)
+                    && other.key_columns.len() > n
+                    && other.key_columns[..n] == index.key_columns[..]
+                    && other.opclasses.get(..n) == index.opclasses.get(..n)
+                    && other.options.get(..n) == index.options.get(..n)
+            });
+            if let Some(covering) = covering {
+                flagged.insert(index.oid);
+                findings.push(Self::index_finding(
+                    IndexFindingKind::Redundant,
+                    index,
+                    Some(covering),
+                    format!("DROP INDEX CONCURRENTLY {};", index.qualified_name),
+                    format!(
+                        "Key columns are a leading prefix of {}",
+                        covering.index_name
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Foreign keys with no valid, non-partial index whose leading key
+    /// columns are the constraint's columns in any order
+    async fn missing_fk_indexes(
+        client: &deadpool_postgres::Client,
+        indexes: &[IndexEntry],
+    ) -> Result<Vec<IndexFinding>, String> {
+        let rows = client
+            .query(
+                r#"
+                SELECT
+                    c.conrelid,
+                    c.conname::text,
+                    n.nspname::text,
+                    t.relname::text,
+                    quote_ident(n.nspname) || '.' || quote_ident(t.relname),
+                    c.conkey,
+                    ARRAY(
+                        SELECT quote_ident(a.attname)
+                        FROM unnest(c.conkey) WITH ORDINALITY AS k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = k.attnum
+                        ORDER BY k.ord
+                    ),
+                    c.confrelid::regclass::text,
+                    pg_relation_size(c.conrelid)
+                FROM pg_constraint c
+                JOIN pg_class t ON t.oid = c.conrelid
+                JOIN pg_namespace n ON n.oid = t.relnamespace
+                WHERE c.contype = 'f'
+                    AND t.relkind = 'r'
+                    AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+                ORDER BY n.nspname, t.relname, c.conname
+                "#,
+                &[],
+            )
+            .await
+            .map_err(|e| format!("Failed to query foreign keys: {}", e))?;
+
+        let mut findings = Vec::new();
+        for row in rows {
+            let table_oid: u32 = row.get(0);
+            let conkey: Vec<i16> = row.get(5);
+            let wanted: HashSet<i16> = conkey.iter().copied().collect();
+
+            let supported = indexes.iter().any(|index| {
+                index.table_oid == table_oid
+                    && index.is_plain()
+                    && index.key_columns.len() >= conkey.len()
+                    && index.key_columns[..conkey.len()]
+                        .iter()
+                        .copied()
+                        .collect::<HashSet<_>>()
+                        == wanted
+            });
+            if supported {
+                continue;
+            }
+
+            let columns: Vec<String> = row.get(6);
+            let referenced: String = row.get(7);
+            let table_bytes: i64 = row.get(8);
+            findings.push(IndexFinding {
+                kind: IndexFindingKind::MissingFkIndex,
+                schemaname: row.get(2),
+                table_name: row.get(3),
+                index_name: None,
+                definition: None,
+                covered_by: None,
+                constraint_name: row.get(1),
+                idx_scan: None,
+                reclaimable_bytes: 0,
+                fix_ddl: format!(
+                    "CREATE INDEX CONCURRENTLY ON {} ({});",
+                    row.get::<_, String>(4),
+                    columns.join(", ")
+                ),
+                reason: format!(
+                    "Deletes and key updates on {} scan the {} MB referencing table for each row",
+                    referenced,
+                    table_bytes / 1024 / 1024
+                ),
+            });
+        }
+
+        Ok(findings)
+    }
+
+    /// Index scans on other connected servers of the same cluster and
+    /// database; physical replicas share index oids with the primary
+    async fn replica_usage(
+        manager: &ConnectionManager,
+        server_id: &str,
+        client: &deadpool_postgres::Client,
+    ) -> (Vec<String>, HashMap<u32, i64>) {
+        let mut checked = Vec::new();
+        let mut scans: HashMap<u32, i64> = HashMap::new();
+
+        let Some(info) = manager.get_server_info(server_id) else {
+            return (checked, scans);
+        };
+        let Some(identifier) = system_identifier(client).await else {
+            return (checked, scans);
+        };
+
+        for peer_id in manager.connected_server_ids() {
+            if peer_id == server_id
+                || manager
+                    .get_server_info(&peer_id)
+                    .is_none_or(|peer| peer.database != info.database)
+            {
+                continue;
+            }
+            let Ok(peer) = manager.get_client(&peer_id).await else {
+                continue;
+            };
+            if system_identifier(&peer).await.as_ref() != Some(&identifier) {
+                continue;
+            }
+
+            let Ok(rows) = peer
+                .query(
+                    "SELECT indexrelid, idx_scan FROM pg_stat_all_indexes WHERE idx_scan > 0",
+                    &[],
+                )
+                .await
+            else {
+                continue;
+            };
+            for row in rows {
+                *scans.entry(row.get(0)).or_default() += row.get::<_, i64>(1);
+            }
+            checked.push(peer_id);
+        }
+
+        (checked, scans)
+    }
+
+    fn index_finding(
+        kind: IndexFindingKind,
+        index: &IndexEntry,
+        covered_by: Option<&IndexEntry>,
+        fix_ddl: String,
+        reason: String,
+    ) -> IndexFinding {
+        IndexFinding {
+            kind,
+            schemaname: index.schemaname.clone(),
+            table_name: index.table_name.clone(),
+            index_name: Some(index.index_name.clone()),
+            definition: Some(index.definition.clone()),
+            covered_by: covered_by.map(|c| c.index_name.clone()),
+            constraint_name: None,
+            idx_scan: Some(index.idx_scan),
+            reclaimable_bytes: index.size_bytes,
+            fix_ddl,
+            reason,
+        }
+    }
+
+    pub fn issues(report: &IndexReport) -> Vec<PerformanceIssue> {
+        report
+            .findings
+            .iter()
+            .map(|finding| {
+                let (issue_type, severity) = match finding.kind {
+                    IndexFindingKind::Invalid => ("Invalid Index", IssueSeverity::Warning),
+                    IndexFindingKind::Duplicate => ("Duplicate Index", IssueSeverity::Warning),
+                    IndexFindingKind::Redundant => ("Redundant Index", IssueSeverity::Info),
+                    IndexFindingKind::MissingFkIndex => {
+                        ("Foreign Key Without Index", IssueSeverity::Warning)
+                    }
+                    IndexFindingKind::Unused => ("Unused Index", IssueSeverity::Info),
+                };
+                let severity = if finding.reclaimable_bytes >= WARNING_BYTES {
+                    IssueSeverity::Warning
+                } else {
+                    severity
+                };
+
+                let subject = match (&finding.index_name, &finding.constraint_name) {
+                    (Some(index), _) => format!(
+                        "Index {}.{} on {} ({} MB)",
+                        finding.schemaname,
+                        index,
+                        finding.table_name,
+                        finding.reclaimable_bytes / 1024 / 1024
+                    ),
+                    (None, Some(constraint)) => format!(
+                        "Foreign key {} on {}.{}",
+                        constraint, finding.schemaname, finding.table_name
+                    ),
+                    (None, None) => format!("{}.{}", finding.schemaname, finding.table_name),
+                };
+
+                PerformanceIssue {
+                    issue_type: issue_type.to_string(),
+                    severity,
+                    description: format!("{}: {}", subject, finding.reason),
+                    recommendation: finding.fix_ddl.clone(),
+                    details: finding.definition.clone(),
+                    object: Some(format!(
+                        "{}.{}",
+                        finding.schemaname,
+                        finding
+                            .index_name
+                            .as_ref()
+                            .or(finding.constraint_name.as_ref())
+                            .unwrap_or(&finding.table_name)
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+async fn system_identifier(client: &deadpool_postgres::Client) -> Option<String> {
+    client
+        .query_one(
+            "SELECT system_identifier::text FROM pg_control_system()",
+            &[],
+        )
+        .await
+        .ok()
+        .map(|row| row.get(0))
+}
+
+/// Elements of an int2vector or oidvector in its text form ("1 3 0")
+fn parse_vector<T: std::str::FromStr>(text: &str) -> Vec<T> {
+    text.split_whitespace()
+        .filter_map(|v| v.parse().ok())
+        .collect()
+}
@@ -0,0 +1,475 @@
+use super::analyzer::{ConfigIssue, IssueSeverity, PerformanceIssue};
+use super::rules::{IssueKind, RuleIssue, RuleRun};
+use super::storage;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueStatus {
+    /// Never seen before
+    New,
+    /// Came back after being resolved, or its severity went up
+    Regressed,
+    /// A snooze expired since the last run
+    Resurfaced,
+    /// Seen on the previous run too
+    Known,
+}
+
+/// Accepted risk when `expires_at` is None, otherwise a snooze
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Acknowledgement {
+    pub comment: String,
+    pub acknowledged_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+/// An issue from the current run with its history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedIssue<T> {
+    pub fingerprint: String,
+    /// Rule that raised the issue
+    pub rule_id: String,
+    pub status: IssueStatus,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    pub acknowledgement: Option<Acknowledgement>,
+    pub issue: T,
+}
+
+/// A stored issue, open or resolved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueRecord {
+    pub fingerprint: String,
+    pub rule_id: String,
+    pub kind: IssueKind,
+    /// The ConfigIssue or PerformanceIssue as last seen
+    pub issue: Value,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    pub resolved_at: Option<i64>,
+    /// Times the issue came back after being resolved or got more severe
+    pub regressions: i64,
+    pub acknowledgement: Option<Acknowledgement>,
+}
+
+/// Issues the history can track
+pub trait TrackableIssue: Serialize + Sized {
+    const KIND: IssueKind;
+    fn from_rule_issue(issue: RuleIssue) -> Option<Self>;
+    fn fingerprint(&self) -> String;
+    fn severity(&self) -> &IssueSeverity;
+}
+
+impl TrackableIssue for ConfigIssue {
+    const KIND: IssueKind = IssueKind::Config;
+    fn from_rule_issue(issue: RuleIssue) -> Option<Self> {
+        match issue {
+            RuleIssue::Config(issue) => Some(issue),
+            RuleIssue::Performance(_) => None,
+        }
+    }
+    fn fingerprint(&self) -> String {
+        ConfigIssue::fingerprint(self)
+    }
+    fn severity(&self) -> &IssueSeverity {
+        &self.severity
+    }
+}
+
+impl TrackableIssue for PerformanceIssue {
+    const KIND: IssueKind = IssueKind::Performance;
+    fn from_rule_issue(issue: RuleIssue) -> Option<Self> {
+        match issue {
+            RuleIssue::Performance(issue) => Some(issue),
+            RuleIssue::Config(_) => None,
+        }
+    }
+    fn fingerprint(&self) -> String {
+        PerformanceIssue::fingerprint(self)
+    }
+    fn severity(&self) -> &IssueSeverity {
+        &self.severity
+    }
+}
+
+/// Stored row of an issue before the current run
+struct StoredIssue {
+    first_seen: i64,
+    severity: i64,
+    resolved_at: Option<i64>,
+    acknowledgement: Option<Acknowledgement>,
+}
+
+/// Analyzer issues per server in SQLite, with first/last seen and resolved
+/// times and acknowledgements
+#[derive(Clone)]
+pub struct IssueHistory {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl IssueHistory {
+    pub fn new() -> Self {
+        storage::data_dir()
+            .ok_or_else(|| "Could not determine data directory".to_string())
+            .and_then(|dir| Self::open(&dir.join("issues.db")))
+            .or_else(|e| {
+                eprintln!("{}; issue history will not be persisted", e);
+                Self::open_in_memory()
+            })
+            .expect("failed to open issue history")
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+
+        let conn =
+            Connection::open(path).map_err(|e| format!("Failed to open issue history: {}", e))?;
+
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open issue history: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            r#"
+            PRAGMA journal_mode = WAL;
+            CREATE TABLE IF NOT EXISTS issues (
+                server_id   TEXT    NOT NULL,
+                fingerprint TEXT    NOT NULL,
+                rule_id     TEXT    NOT NULL,
+                kind        TEXT    NOT NULL,
+                issue       TEXT    NOT NULL,
+                severity    INTEGER NOT NULL,
+                first_seen  INTEGER NOT NULL,
+                last_seen   INTEGER NOT NULL,
+                resolved_at INTEGER,
+                regressions INTEGER NOT NULL DEFAULT 0,
+                ack_comment TEXT,
+                ack_at      INTEGER,
+                ack_until   INTEGER,
+                PRIMARY KEY (server_id, fingerprint)
+            ) WITHOUT ROWID;
+            "#,
+        )
+        .map_err(|e| format!("Failed to initialize issue history: {}", e))?;
+
+        Ok(IssueHistory {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Store the issues of one analyzer run. Open issues of rules that ran
+    /// but no longer report them are marked resolved; issues of rules that
+    /// were skipped, disabled or lacked data are left open. Reappearing or
+    /// more severe issues lose their acknowledgement.
+    pub fn record<T: TrackableIssue>(
+        &self,
+        server_id: &str,
+        run: RuleRun,
+        now: i64,
+    ) -> Result<Vec<TrackedIssue<T>>, String> {
+        let kind = kind_str(T::KIND);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        // Status of each fingerprint already stored in this run; repeats
+        // are returned with it rather than stored twice
+        let mut seen: HashMap<String, (IssueStatus, i64, Option<Acknowledgement>)> = HashMap::new();
+        let mut tracked = Vec::new();
+
+        for (rule_id, issue) in run.issues {
+            let Some(issue) = T::from_rule_issue(issue) else {
+                continue;
+            };
+            let fingerprint = issue.fingerprint();
+            if let Some((status, first_seen, acknowledgement)) = seen.get(&fingerprint) {
+                tracked.push(TrackedIssue {
+                    fingerprint,
+                    rule_id,
+                    status: *status,
+                    first_seen: *first_seen,
+                    last_seen: now,
+                    acknowledgement: acknowledgement.clone(),
+                    issue,
+                });
+                continue;
+            }
+            let severity = severity_rank(issue.severity());
+            let json = serde_json::to_string(&issue)
+                .map_err(|e| format!("Failed to serialize issue: {}", e))?;
+
+            let stored = tx
+                .query_row(
+                    r#"
+                    SELECT first_seen, severity, resolved_at, ack_comment, ack_at, ack_until
+                    FROM issues
+                    WHERE server_id = ?1 AND fingerprint = ?2
+                    "#,
+                    params![server_id, fingerprint],
+                    |row| {
+                        Ok(StoredIssue {
+                            first_seen: row.get(0)?,
+                            severity: row.get(1)?,
+                            resolved_at: row.get(2)?,
+                            acknowledgement: acknowledgement(row.get(3)?, row.get(4)?, row.get(5)?),
+                        })
+                    },
+                )
+                .optional()
+                .map_err(|e| format!("Failed to read issue history: {}", e))?;
+
+            let (status, first_seen, acknowledgement) = match stored {
+                None => (IssueStatus::New, now, None),
+                Some(stored) if stored.resolved_at.is_some() || severity > stored.severity => {
+                    (IssueStatus::Regressed, stored.first_seen, None)
+                }
+                Some(StoredIssue {
+                    first_seen,
+                    acknowledgement: Some(ack),
+                    ..
+                }) if ack.expires_at.is_some_and(|until| until <= now) => {
+                    (IssueStatus::Resurfaced, first_seen, None)
+                }
+                Some(stored) => (
+                    IssueStatus::Known,
+                    stored.first_seen,
+                    stored.acknowledgement,
+                ),
+            };
+
+            match status {
+                IssueStatus::New => tx.execute(
+                    r#"
+                    INSERT INTO issues
+                        (server_id, fingerprint, rule_id, kind, issue, severity,
+                         first_seen, last_seen)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+                    "#,
+                    params![server_id, fingerprint, rule_id, kind, json, severity, now],
+                ),
+                IssueStatus::Regressed | IssueStatus::Resurfaced => tx.execute(
+                    r#"
+                    UPDATE issues
+                    SET rule_id = ?3, issue = ?4, severity = ?5, last_seen = ?6,
+                        resolved_at = NULL, regressions = regressions + ?7,
+                        ack_comment = NULL, ack_at = NULL, ack_until = NULL
+                    WHERE server_id = ?1 AND fingerprint = ?2
+                    "#,
+                    params![
+                        server_id,
+                        fingerprint,
+                        rule_id,
+                        json,
+                        severity,
+                        now,
+                        (status == IssueStatus::Regressed) as i64,
+                    ],
+                ),
+                IssueStatus::Known => tx.execute(
+                    r#"
+                    UPDATE issues
+                    SET rule_id = ?3, issue = ?4, severity = ?5, last_seen = ?6
+                    WHERE server_id = ?1 AND fingerprint = ?2
+                    "#,
+                    params![server_id, fingerprint, rule_id, json, severity, now],
+                ),
+            }
+            .map_err(|e| format!("Failed to store issue: {}", e))?;
+
+            seen.insert(
+                fingerprint.clone(),
+                (status, first_seen, acknowledgement.clone()),
+            );
+            tracked.push(TrackedIssue {
+                fingerprint,
+                rule_id,
+                status,
+                first_seen,
+                last_seen: now,
+                acknowledgement,
+                issue,
+            });
+        }
+
+        let open: Vec<(String, String)> = {
+            let mut stmt = tx
+                .prepare(
+                    r#"
+                    SELECT fingerprint, rule_id FROM issues
+                    WHERE server_id = ?1 AND kind = ?2 AND resolved_at IS NULL
+                    "#,
+                )
+                .map_err(|e| format!("Failed to prepare issue query: {}", e))?;
+            let rows = stmt
+                .query_map(params![server_id, kind], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .map_err(|e| format!("Failed to read issue history: {}", e))?;
+            rows.collect::<Result<_, _>>()
+                .map_err(|e| format!("Failed to read issue history: {}", e))?
+        };
+
+        let resolved = open.iter().filter(|(fingerprint, rule_id)| {
+            !seen.contains_key(fingerprint) && run.evaluated.contains(rule_id)
+        });
+        for (fingerprint, _) in resolved {
+            tx.execute(
+                "UPDATE issues SET resolved_at = ?3 WHERE server_id = ?1 AND fingerprint = ?2",
+                params![server_id, fingerprint, now],
+            )
+            .map_err(|e| format!("Failed to resolve issue: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit issue history: {}", e))?;
+
+        Ok(tracked)
+    }
+
+    /// Accept an issue, or snooze it until `expires_at`
+    pub fn acknowledge(
+        &self,
+        server_id: &str,
+        fingerprint: &str,
+        comment: &str,
+        expires_at: Option<i64>,
+        now: i64,
+    ) -> Result<(), String> {
+        if expires_at.is_some_and(|until| until <= now) {
+            return Err("Snooze expiry must be in the future".to_string());
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let updated = conn
+            .execute(
+                r#"
+                UPDATE issues SET ack_comment = ?3, ack_at = ?4, ack_until = ?5
+                WHERE server_id = ?1 AND fingerprint = ?2
+                "#,
+                params![server_id, fingerprint, comment, now, expires_at],
+            )
+            .map_err(|e| format!("Failed to acknowledge issue: {}", e))?;
+
+        if updated == 0 {
+            return Err(format!("Unknown issue: {}", fingerprint));
+        }
+        Ok(())
+    }
+
+    pub fn clear_acknowledgement(&self, server_id: &str, fingerprint: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            r#"
+            UPDATE issues SET ack_comment = NULL, ack_at = NULL, ack_until = NULL
+            WHERE server_id = ?1 AND fingerprint = ?2
+            "#,
+            params![server_id, fingerprint],
+        )
+        .map_err(|e| format!("Failed to clear acknowledgement: {}", e))?;
+        Ok(())
+    }
+
+    /// Stored issues of a server, most recently seen first
+    pub fn list(
+        &self,
+        server_id: &str,
+        include_resolved: bool,
+    ) -> Result<Vec<IssueRecord>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                r#"
+                SELECT fingerprint, kind, issue, first_seen, last_seen, resolved_at,
+                    regressions, ack_comment, ack_at, ack_until, rule_id
+                FROM issues
+                WHERE server_id = ?1 AND (?2 OR resolved_at IS NULL)
+                ORDER BY last_seen DESC, fingerprint
+                "#,
+            )
+            .map_err(|e| format!("Failed to prepare issue query: {}", e))?;
+
+        let rows = stmt
+            .query_map(params![server_id, include_resolved], |row| {
+                let kind: String = row.get(1)?;
+                let issue: String = row.get(2)?;
+                Ok(IssueRecord {
+                    fingerprint: row.get(0)?,
+                    rule_id: row.get(10)?,
+                    kind: if kind == kind_str(IssueKind::Performance) {
+                        IssueKind::Performance
+                    } else {
+                        IssueKind::Config
+                    },
+                    issue: serde_json::from_str(&issue).unwrap_or(Value::Null),
+                    first_seen: row.get(3)?,
+                    last_seen: row.get(4)?,
+                    resolved_at: row.get(5)?,
+                    regressions: row.get(6)?,
+                    acknowledgement: acknowledgement(row.get(7)?, row.get(8)?, row.get(9)?),
+                })
+            })
+            .map_err(|e| format!("Failed to query issue history: {}", e))?;
+
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read issue history: {}", e))
+    }
+
+    pub fn forget(&self, server_id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM issues WHERE server_id = ?1",
+            params![server_id],
+        )
+        .map_err(|e| format!("Failed to delete issue history: {}", e))?;
+        Ok(())
+    }
+}
+
+impl Default for IssueHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn kind_str(kind: IssueKind) -> &'static str {
+    match kind {
+        IssueKind::Config => "config",
+        IssueKind::Performance => "performance",
+    }
+}
+
+fn severity_rank(severity: &IssueSeverity) -> i64 {
+    match severity {
+        IssueSeverity::Info => 1,
+        IssueSeverity::Warning => 2,
+        IssueSeverity::Critical => 3,
+    }
+}
+
+fn acknowledgement(
+    comment: Option<String>,
+    acknowledged_at: Option<i64>,
+    expires_at: Option<i64>,
+) -> Option<Acknowledgement> {
+    Some(Acknowledgement {
+        comment: comment?,
+        acknowledged_at: acknowledged_at?,
+        expires_at,
+    })
+}
@@ -0,0 +1,258 @@
+use super::connection::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A backend in a blocking chain, with the backends it blocks as children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockingNode {
+    pub pid: i32,
+    pub usename: Option<String>,
+    pub datname: Option<String>,
+    pub application_name: Option<String>,
+    pub state: Option<String>,
+    pub query: Option<String>,
+    pub wait_event_type: Option<String>,
+    pub wait_event: Option<String>,
+    /// Lock this backend is waiting for, if any
+    pub lock_mode: Option<String>,
+    pub locktype: Option<String>,
+    pub relation: Option<String>,
+    /// Seconds spent waiting for the lock (state change before PG14)
+    pub wait_secs: Option<f64>,
+    pub xact_secs: Option<f64>,
+    pub blocked_by: Vec<i32>,
+    /// Number of backends waiting on this one, directly or transitively
+    pub blocked_count: usize,
+    /// Part of a wait cycle that the deadlock detector has not resolved yet
+    pub in_cycle: bool,
+    pub children: Vec<BlockingNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockingChain {
+    /// Root blocker first
+    pub pids: Vec<i32>,
+    pub depth: usize,
+    /// Longest wait along the chain
+    pub max_wait_secs: Option<f64>,
+    pub root_query: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockTree {
+    /// Backends that block others without waiting themselves
+    pub roots: Vec<BlockingNode>,
+    pub waiting_count: usize,
+    pub deepest_chain: Option<BlockingChain>,
+}
+
+pub struct LockCollector;
+
+impl LockCollector {
+    pub async fn get_lock_tree(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<LockTree, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let client = manager.get_client(server_id).await?;
+
+        // pg_locks.waitstart exists since PG14
+        let waitstart = if capabilities.version_num >= 140000 {
+            "waitstart"
+        } else {
+            "NULL::timestamptz AS waitstart"
+        };
+
+        let query = format!(
+            r#"
+            WITH waiting AS (
+                SELECT pid, pg_blocking_pids(pid) AS blocked_by
+                FROM pg_stat_activity
+                WHERE cardinality(pg_blocking_pids(pid)) > 0
+            ),
+            involved AS (
+                SELECT pid FROM waiting
+                UNION
+                SELECT unnest(blocked_by) FROM waiting
+            )
+            SELECT
+                a.pid,
+                a.usename::text,
+                a.datname::text,
+                a.application_name,
+                a.state,
+                a.query,
+                a.wait_event_type,
+                a.wait_event,
+                l.mode,
+                l.locktype,
+                l.relation::regclass::text,
+                EXTRACT(EPOCH FROM now() - COALESCE(l.waitstart, a.state_change))::float8,
+                EXTRACT(EPOCH FROM now() - a.xact_start)::float8,
+                COALESCE(w.blocked_by, '{{}}'::int[])
+            FROM involved i
+            JOIN pg_stat_activity a ON a.pid = i.pid
+            LEFT JOIN waiting w ON w.pid = a.pid
+            LEFT JOIN LATERAL (
+                SELECT mode, locktype, relation, {waitstart}
+                FROM pg_locks
+                WHERE pid = a.pid AND NOT granted
+                LIMIT 1
+            ) l ON true
+            "#,
+        );
+
+        let rows = client
+            .query(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to query blocking locks: {}", e))?;
+
+        let nodes: HashMap<i32, BlockingNode> = rows
+            .iter()
+            .map(|row| {
+                let waiting = !row.get::<_, Vec<i32>>(13).is_empty();
+                let node = BlockingNode {
+                    pid: row.get(0),
+                    usename: row.get(1),
+                    datname: row.get(2),
+                    application_name: row.get(3),
+                    state: row.get(4),
+                    query: row.get(5),
+                    wait_event_type: row.get(6),
+                    wait_event: row.get(7),
+                    lock_mode: row.get(8),
+                    locktype: row.get(9),
+                    relation: row.get(10),
+                    wait_secs: if waiting { row.get(11) } else { None },
+                    xact_secs: row.get(12),
+                    blocked_by: row.get(13),
+                    blocked_count: 0,
+                    in_cycle: false,
+                    children: Vec::new(),
+                };
+                (node.pid, node)
+            })
+            .collect();
+
+        Ok(build_tree(nodes))
+    }
+}
+
+/// Turn flat waiter -> blockers edges into trees rooted at the blockers
+/// that are not waiting themselves. A waiter blocked by several backends
+/// appears under each of them.
+fn build_tree(nodes: HashMap<i32, BlockingNode>) -> LockTree {
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for node in nodes.values() {
+        for blocker in &node.blocked_by {
+            children.entry(*blocker).or_default().push(node.pid);
+        }
+    }
+    for list in children.values_mut() {
+        list.sort();
+    }
+
+    let waiting_count = nodes.values().filter(|n| !n.blocked_by.is_empty()).count();
+
+    let mut root_pids: Vec<i32> = nodes
+        .values()
+        .filter(|n| n.blocked_by.is_empty())
+        .map(|n| n.pid)
+        .collect();
+    root_pids.sort();
+
+    let mut visited = HashSet::new();
+    let mut roots: Vec<BlockingNode> = root_pids
+        .iter()
+        .map(|pid| expand(*pid, &nodes, &children, &mut Vec::new(), &mut visited))
+        .collect();
+
+    // Whatever is left is either a cycle not yet broken by the deadlock
+    // detector or waits on a blocker without a backend (a prepared
+    // transaction reports pid 0). Report each once from its lowest pid.
+    let mut remaining: Vec<i32> = nodes
+        .keys()
+        .filter(|pid| !visited.contains(*pid))
+        .copied()
+        .collect();
+    remaining.sort();
+    for pid in remaining {
+        if visited.contains(&pid) {
+            continue;
+        }
+        roots.push(expand(pid, &nodes, &children, &mut Vec::new(), &mut visited));
+    }
+
+    roots.sort_by(|a, b| b.blocked_count.cmp(&a.blocked_count).then(a.pid.cmp(&b.pid)));
+
+    let deepest_chain = roots
+        .iter()
+        .filter_map(|root| {
+            let mut best = Vec::new();
+            deepest_path(root, &mut Vec::new(), &mut best);
+            (best.len() > 1).then_some((root, best))
+        })
+        .max_by_key(|(_, path)| path.len())
+        .map(|(root, path)| BlockingChain {
+            depth: path.len() - 1,
+            max_wait_secs: path.iter().filter_map(|(_, w)| *w).reduce(f64::max),
+            pids: path.iter().map(|(pid, _)| *pid).collect(),
+            root_query: root.query.clone(),
+        });
+
+    LockTree {
+        roots,
+        waiting_count,
+        deepest_chain,
+    }
+}
+
+fn expand(
+    pid: i32,
+    nodes: &HashMap<i32, BlockingNode>,
+    children: &HashMap<i32, Vec<i32>>,
+    path: &mut Vec<i32>,
+    visited: &mut HashSet<i32>,
+) -> BlockingNode {
+    let mut node = nodes[&pid].clone();
+    visited.insert(pid);
+    path.push(pid);
+
+    for child in children.get(&pid).into_iter().flatten() {
+        if path.contains(child) {
+            node.in_cycle = true;
+            continue;
+        }
+        node.children.push(expand(*child, nodes, children, path, visited));
+    }
+
+    path.pop();
+
+    let mut descendants = HashSet::new();
+    collect_pids(&node.children, &mut descendants);
+    node.blocked_count = descendants.len();
+
+    node
+}
+
+fn collect_pids(nodes: &[BlockingNode], pids: &mut HashSet<i32>) {
+    for node in nodes {
+        pids.insert(node.pid);
+        collect_pids(&node.children, pids);
+    }
+}
+
+fn deepest_path(
+    node: &BlockingNode,
+    path: &mut Vec<(i32, Option<f64>)>,
+    best: &mut Vec<(i32, Option<f64>)>,
+) {
+    path.push((node.pid, node.wait_secs));
+    if path.len() > best.len() {
+        *best = path.clone();
+    }
+    for child in &node.children {
+        deepest_path(child, path, best);
+    }
+    path.pop();
+}
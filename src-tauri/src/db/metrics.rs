@@ -17,12 +17,20 @@ pub struct DatabaseStats {
     pub temp_files: i64,
     pub temp_bytes: i64,
     pub deadlocks: i64,
+    pub stats_reset: Option<String>,
 }
 
+/// One pg_stat_statements entry. Fields missing from the installed
+/// extension version are None.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryStat {
+    /// None when query id computation is disabled
+    pub queryid: Option<i64>,
+    pub userid: u32,
+    pub dbid: u32,
     pub query: String,
     pub calls: i64,
+    /// Before 1.8 these map to total_time/mean_time/min_time/max_time
     pub total_exec_time: f64,
     pub mean_exec_time: f64,
     pub min_exec_time: f64,
@@ -30,6 +38,34 @@ pub struct QueryStat {
     pub rows: i64,
     pub shared_blks_hit: i64,
     pub shared_blks_read: i64,
+    pub local_blks_read: i64,
+    pub temp_blks_read: i64,
+    pub temp_blks_written: i64,
+    /// 1.8+ (PG13)
+    pub plans: Option<i64>,
+    pub total_plan_time: Option<f64>,
+    pub wal_bytes: Option<i64>,
+    /// 1.10+ (PG15)
+    pub jit_functions: Option<i64>,
+    pub jit_generation_time: Option<f64>,
+    pub jit_inlining_time: Option<f64>,
+    pub jit_optimization_time: Option<f64>,
+    pub jit_emission_time: Option<f64>,
+}
+
+/// Ordering of `get_top_queries`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuerySortKey {
+    #[default]
+    TotalTime,
+    MeanTime,
+    Calls,
+    /// Blocks read from disk (shared + local + temp)
+    Io,
+    /// Temp blocks written
+    Temp,
+    Wal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,6 +118,9 @@ pub struct LockInfo {
     pub granted: bool,
 }
 
+/// Checkpointer and background writer counters, unified across versions.
+/// On PG17+ checkpoint fields come from pg_stat_checkpointer and
+/// `buffers_backend` is derived from pg_stat_io.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BgWriterStats {
     pub checkpoints_timed: i64,
@@ -91,8 +130,88 @@ pub struct BgWriterStats {
     pub buffers_checkpoint: i64,
     pub buffers_clean: i64,
     pub maxwritten_clean: i64,
-    pub buffers_backend: i64,
+    pub buffers_backend: Option<i64>,
     pub buffers_alloc: i64,
+    pub stats_reset: Option<String>,
+    /// PG17+ only
+    pub restartpoints_timed: Option<i64>,
+    pub restartpoints_req: Option<i64>,
+    pub restartpoints_done: Option<i64>,
+    /// pg_stat_checkpointer reset time (PG17+ only)
+    pub checkpointer_stats_reset: Option<String>,
+}
+
+/// A standby or WAL sender connected to this server (pg_stat_replication)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationStat {
+    pub pid: i32,
+    pub usename: Option<String>,
+    pub application_name: Option<String>,
+    pub client_addr: Option<String>,
+    pub state: Option<String>,
+    pub sync_state: Option<String>,
+    pub sync_priority: Option<i32>,
+    pub sent_lsn: Option<String>,
+    pub write_lsn: Option<String>,
+    pub flush_lsn: Option<String>,
+    pub replay_lsn: Option<String>,
+    /// Lag intervals in seconds
+    pub write_lag_secs: Option<f64>,
+    pub flush_lag_secs: Option<f64>,
+    pub replay_lag_secs: Option<f64>,
+    /// WAL bytes not yet replayed on the standby
+    pub replay_lag_bytes: Option<i64>,
+    pub backend_start: Option<String>,
+    /// PG12+
+    pub reply_time: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationSlot {
+    pub slot_name: String,
+    pub plugin: Option<String>,
+    pub slot_type: String,
+    pub database: Option<String>,
+    pub active: bool,
+    pub active_pid: Option<i32>,
+    pub temporary: bool,
+    pub restart_lsn: Option<String>,
+    pub confirmed_flush_lsn: Option<String>,
+    /// WAL kept on disk for this slot
+    pub retained_wal_bytes: Option<i64>,
+    /// PG13+
+    pub wal_status: Option<String>,
+    pub safe_wal_size: Option<i64>,
+}
+
+/// Standby side of streaming replication (pg_stat_wal_receiver)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalReceiverStat {
+    pub pid: i32,
+    pub status: String,
+    pub sender_host: Option<String>,
+    pub sender_port: Option<i32>,
+    pub slot_name: Option<String>,
+    /// Last LSN flushed to disk (received_lsn before PG13)
+    pub flushed_lsn: Option<String>,
+    pub latest_end_lsn: Option<String>,
+    pub latest_end_time: Option<String>,
+    pub last_msg_send_time: Option<String>,
+    pub last_msg_receipt_time: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryStatus {
+    pub in_recovery: bool,
+    /// The remaining fields are only set on a standby
+    pub replay_paused: Option<bool>,
+    pub last_receive_lsn: Option<String>,
+    pub last_replay_lsn: Option<String>,
+    /// WAL received but not yet replayed
+    pub replay_lag_bytes: Option<i64>,
+    pub last_replay_timestamp: Option<String>,
+    /// Age of the last replayed transaction; 0 when replay has caught up
+    pub replay_lag_secs: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -106,6 +225,8 @@ pub struct DatabaseSize {
 pub struct ExplainNode {
     pub node_type: String,
     pub relation_name: Option<String>,
+    /// Schema of relation_name (VERBOSE output only)
+    pub schema: Option<String>,
     pub alias: Option<String>,
     pub startup_cost: f64,
     pub total_cost: f64,
@@ -1,10 +1,34 @@
 pub mod types;
+pub mod capabilities;
 pub mod connection;
 pub mod metrics;
 pub mod queries;
 pub mod config;
 pub mod analyzer;
 pub mod storage;
+pub mod tls;
+pub mod tunnel;
+pub mod import;
+pub mod history;
+pub mod sampler;
+pub mod rates;
+pub mod statements;
+pub mod locks;
+pub mod backends;
+pub mod topology;
+pub mod progress;
+pub mod wraparound;
+pub mod bloat;
+pub mod sizes;
+pub mod indexes;
+pub mod hypothetical;
+pub mod tuning;
+pub mod settings;
+pub mod rules;
+pub mod analyzer_config;
+pub mod issue_history;
+pub mod health;
+pub mod server_data;
 
 pub use types::*;
 pub use connection::*;
@@ -13,3 +37,24 @@ pub use queries::*;
 pub use config::*;
 pub use analyzer::*;
 pub use storage::*;
+pub use import::*;
+pub use history::*;
+pub use sampler::*;
+pub use rates::*;
+pub use statements::*;
+pub use locks::*;
+pub use backends::*;
+pub use topology::*;
+pub use progress::*;
+pub use wraparound::*;
+pub use bloat::*;
+pub use sizes::*;
+pub use indexes::*;
+pub use hypothetical::*;
+pub use tuning::*;
+pub use settings::*;
+pub use rules::*;
+pub use analyzer_config::*;
+pub use issue_history::*;
+pub use health::*;
+pub use server_data::*;
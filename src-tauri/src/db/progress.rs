@@ -0,0 +1,267 @@
+use super::connection::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressKind {
+    Vacuum,
+    Analyze,
+    CreateIndex,
+    Cluster,
+    Basebackup,
+    Copy,
+}
+
+impl ProgressKind {
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "vacuum" => Some(ProgressKind::Vacuum),
+            "analyze" => Some(ProgressKind::Analyze),
+            "create_index" => Some(ProgressKind::CreateIndex),
+            "cluster" => Some(ProgressKind::Cluster),
+            "basebackup" => Some(ProgressKind::Basebackup),
+            "copy" => Some(ProgressKind::Copy),
+            _ => None,
+        }
+    }
+}
+
+/// One running maintenance operation from a pg_stat_progress_* view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationProgress {
+    pub kind: ProgressKind,
+    pub pid: i32,
+    pub datname: Option<String>,
+    /// Table being processed; only resolvable in the connected database,
+    /// other databases report the oid
+    pub relation: Option<String>,
+    /// Index being built (create_index only)
+    pub index_relation: Option<String>,
+    pub phase: Option<String>,
+    /// Command variant, e.g. "CREATE INDEX CONCURRENTLY" or "COPY FROM"
+    pub command: Option<String>,
+    pub done: Option<i64>,
+    pub total: Option<i64>,
+    /// Unit of done/total: blocks, tuples or bytes
+    pub unit: String,
+    pub percent: Option<f64>,
+    /// Units per second since the previous sample of the same phase
+    pub rate_per_sec: Option<f64>,
+    pub eta_secs: Option<f64>,
+    pub usename: Option<String>,
+    pub query: Option<String>,
+    pub elapsed_secs: Option<f64>,
+}
+
+/// Server id, operation kind and backend pid
+type ProgressKey = (String, ProgressKind, i32);
+
+struct ProgressSample {
+    taken_at: Instant,
+    phase: Option<String>,
+    done: i64,
+}
+
+/// Collects progress views and derives ETAs by comparing each operation
+/// with its previous sample
+#[derive(Clone, Default)]
+pub struct ProgressTracker {
+    previous: Arc<Mutex<HashMap<ProgressKey, ProgressSample>>>,
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get_progress(
+        &self,
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<Vec<OperationProgress>, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let client = manager.get_client(server_id).await?;
+
+        let query = Self::progress_query(capabilities.version_num);
+
+        let rows = client
+            .query(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to query progress views: {}", e))?;
+
+        let now = Instant::now();
+        let mut operations: Vec<OperationProgress> = rows
+            .iter()
+            .filter_map(|row| {
+                let kind = ProgressKind::parse(row.get::<_, &str>(0))?;
+                let done: Option<i64> = row.get(7);
+                let total: Option<i64> = row.get(8);
+                Some(OperationProgress {
+                    kind,
+                    pid: row.get(1),
+                    datname: row.get(2),
+                    relation: row.get(3),
+                    index_relation: row.get(4),
+                    phase: row.get(5),
+                    command: row.get(6),
+                    done,
+                    total,
+                    unit: row.get(9),
+                    percent: match (done, total) {
+                        (Some(done), Some(total)) if total > 0 => {
+                            Some((done as f64 / total as f64 * 100.0).min(100.0))
+                        }
+                        _ => None,
+                    },
+                    rate_per_sec: None,
+                    eta_secs: None,
+                    usename: row.get(10),
+                    query: row.get(11),
+                    elapsed_secs: row.get(12),
+                })
+            })
+            .collect();
+
+        let mut previous = self.previous.lock().unwrap();
+        previous.retain(|(id, kind, pid), _| {
+            id != server_id
+                || operations
+                    .iter()
+                    .any(|op| op.kind == *kind && op.pid == *pid)
+        });
+
+        for op in &mut operations {
+            let key = (server_id.to_string(), op.kind, op.pid);
+            let Some(done) = op.done else {
+                previous.remove(&key);
+                continue;
+            };
+
+            if let Some(prev) = previous.get(&key) {
+                let secs = now.duration_since(prev.taken_at).as_secs_f64();
+                if prev.phase == op.phase && secs > 0.0 && done >= prev.done {
+                    let rate = (done - prev.done) as f64 / secs;
+                    op.rate_per_sec = Some(rate);
+                    op.eta_secs = match op.total {
+                        Some(total) if rate > 0.0 && total >= done => {
+                            Some((total - done) as f64 / rate)
+                        }
+                        _ => None,
+                    };
+                }
+            }
+
+            previous.insert(
+                key,
+                ProgressSample {
+                    taken_at: now,
+                    phase: op.phase.clone(),
+                    done,
+                },
+            );
+        }
+
+        Ok(operations)
+    }
+
+    pub fn forget(&self, server_id: &str) {
+        self.previous
+            .lock()
+            .unwrap()
+            .retain(|(id, _, _), _| id != server_id);
+    }
+
+    /// UNION of the progress views available on this version; every branch
+    /// yields kind, pid, datname, relation, index, phase, command, done,
+    /// total, unit
+    fn progress_query(version_num: i32) -> String {
+        let relation = |col: &str| {
+            format!(
+                "CASE WHEN p.datname = current_database() THEN p.{col}::regclass::text ELSE p.{col}::text END"
+            )
+        };
+
+        let mut branches = vec![format!(
+            r#"
+            SELECT 'vacuum', p.pid, p.datname::text, {rel}, NULL::text, p.phase, NULL::text,
+                CASE WHEN p.phase = 'vacuuming heap' THEN p.heap_blks_vacuumed
+                     ELSE p.heap_blks_scanned END,
+                p.heap_blks_total, 'blocks'
+            FROM pg_stat_progress_vacuum p
+            "#,
+            rel = relation("relid")
+        )];
+
+        if version_num >= 120000 {
+            branches.push(format!(
+                r#"
+                SELECT 'create_index', p.pid, p.datname::text, {rel}, {idx}, p.phase, p.command,
+                    CASE WHEN p.blocks_total > 0 THEN p.blocks_done ELSE p.tuples_done END,
+                    CASE WHEN p.blocks_total > 0 THEN p.blocks_total ELSE p.tuples_total END,
+                    CASE WHEN p.blocks_total > 0 THEN 'blocks' ELSE 'tuples' END
+                FROM pg_stat_progress_create_index p
+                "#,
+                rel = relation("relid"),
+                idx = relation("index_relid")
+            ));
+            branches.push(format!(
+                r#"
+                SELECT 'cluster', p.pid, p.datname::text, {rel}, NULL::text, p.phase, p.command,
+                    p.heap_blks_scanned, p.heap_blks_total, 'blocks'
+                FROM pg_stat_progress_cluster p
+                "#,
+                rel = relation("relid")
+            ));
+        }
+
+        if version_num >= 130000 {
+            branches.push(format!(
+                r#"
+                SELECT 'analyze', p.pid, p.datname::text, {rel}, NULL::text, p.phase, NULL::text,
+                    p.sample_blks_scanned, p.sample_blks_total, 'blocks'
+                FROM pg_stat_progress_analyze p
+                "#,
+                rel = relation("relid")
+            ));
+            branches.push(
+                r#"
+                SELECT 'basebackup', p.pid, NULL::text, NULL::text, NULL::text, p.phase, NULL::text,
+                    p.backup_streamed, p.backup_total, 'bytes'
+                FROM pg_stat_progress_basebackup p
+                "#
+                .to_string(),
+            );
+        }
+
+        if version_num >= 140000 {
+            branches.push(format!(
+                r#"
+                SELECT 'copy', p.pid, p.datname::text, {rel}, NULL::text, NULL::text, p.command,
+                    CASE WHEN p.bytes_total > 0 THEN p.bytes_processed ELSE p.tuples_processed END,
+                    NULLIF(p.bytes_total, 0),
+                    CASE WHEN p.bytes_total > 0 THEN 'bytes' ELSE 'tuples' END
+                FROM pg_stat_progress_copy p
+                "#,
+                rel = relation("relid")
+            ));
+        }
+
+        format!(
+            r#"
+            SELECT
+                o.kind, o.pid, o.datname, o.relation, o.index_relation, o.phase, o.command,
+                o.done, o.total, o.unit,
+                a.usename::text,
+                a.query,
+                EXTRACT(EPOCH FROM now() - COALESCE(a.query_start, a.backend_start))::float8
+            FROM ({}) AS o (kind, pid, datname, relation, index_relation, phase, command, done, total, unit)
+            LEFT JOIN pg_stat_activity a ON a.pid = o.pid
+            ORDER BY o.kind, o.pid
+            "#,
+            branches.join(" UNION ALL ")
+        )
+    }
+}
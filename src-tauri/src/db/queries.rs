@@ -1,3 +1,4 @@
+use super::capabilities::{ExtensionVersion, ServerCapabilities};
 use super::connection::ConnectionManager;
 use super::metrics::*;
 use serde_json::Value;
@@ -27,7 +28,8 @@ impl MetricsCollector {
                 conflicts,
                 temp_files,
                 temp_bytes,
-                deadlocks
+                deadlocks,
+                stats_reset::text
             FROM pg_stat_database
             WHERE datname NOT IN ('template0', 'template1')
         "#;
@@ -55,6 +57,7 @@ impl MetricsCollector {
                 temp_files: row.get(12),
                 temp_bytes: row.get(13),
                 deadlocks: row.get(14),
+                stats_reset: row.get(15),
             })
             .collect();
 
@@ -65,60 +68,153 @@ impl MetricsCollector {
         manager: &ConnectionManager,
         server_id: &str,
         limit: i32,
+        sort: QuerySortKey,
     ) -> Result<Vec<QueryStat>, String> {
         let client = manager.get_client(server_id).await?;
 
-        // Check if pg_stat_statements extension exists
-        let check_ext = client
-            .query(
-                "SELECT 1 FROM pg_extension WHERE extname = 'pg_stat_statements'",
+        let Some(version) = Self::pg_stat_statements_version(&client).await? else {
+            // Return empty list instead of error when extension is not installed
+            return Ok(vec![]);
+        };
+
+        let query = Self::statements_query(version, Some(sort))?;
+
+        let rows = client
+            .query(&query, &[&(limit as i64)])
+            .await
+            .map_err(|e| format!("Failed to query pg_stat_statements: {}", e))?;
+
+        Ok(rows.iter().map(Self::query_stat_from_row).collect())
+    }
+
+    /// Installed pg_stat_statements version, or None if the extension is missing
+    pub async fn pg_stat_statements_version(
+        client: &tokio_postgres::Client,
+    ) -> Result<Option<ExtensionVersion>, String> {
+        let row = client
+            .query_opt(
+                "SELECT extversion FROM pg_extension WHERE extname = 'pg_stat_statements'",
                 &[],
             )
             .await
             .map_err(|e| format!("Failed to check pg_stat_statements: {}", e))?;
 
-        if check_ext.is_empty() {
-            // Return empty list instead of error when extension is not installed
-            return Ok(vec![]);
+        match row {
+            Some(row) => {
+                let extversion: String = row.get(0);
+                ExtensionVersion::parse(&extversion)
+                    .map(Some)
+                    .ok_or_else(|| format!("Unrecognized pg_stat_statements version: {}", extversion))
+            }
+            None => Ok(None),
         }
+    }
 
-        let query = r#"
+    /// Build a pg_stat_statements query whose columns match
+    /// `query_stat_from_row` for the given extension version. Without a sort
+    /// key all entries are returned; otherwise `$1` is the row limit.
+    pub fn statements_query(
+        version: ExtensionVersion,
+        sort: Option<QuerySortKey>,
+    ) -> Result<String, String> {
+        let has_exec_time = version.at_least(1, 8);
+        let has_jit = version.at_least(1, 10);
+
+        let (total, mean, min, max) = if has_exec_time {
+            ("total_exec_time", "mean_exec_time", "min_exec_time", "max_exec_time")
+        } else {
+            ("total_time", "mean_time", "min_time", "max_time")
+        };
+
+        let (plans, plan_time, wal_bytes) = if has_exec_time {
+            ("plans", "total_plan_time", "wal_bytes::bigint")
+        } else {
+            ("NULL::bigint", "NULL::float8", "NULL::bigint")
+        };
+
+        let jit = if has_jit {
+            "jit_functions, jit_generation_time, jit_inlining_time, jit_optimization_time, jit_emission_time"
+        } else {
+            "NULL::bigint, NULL::float8, NULL::float8, NULL::float8, NULL::float8"
+        };
+
+        let order = match sort {
+            None => String::new(),
+            Some(sort) => {
+                let key = match sort {
+                    QuerySortKey::TotalTime => total.to_string(),
+                    QuerySortKey::MeanTime => mean.to_string(),
+                    QuerySortKey::Calls => "calls".to_string(),
+                    QuerySortKey::Io => {
+                        "shared_blks_read + local_blks_read + temp_blks_read".to_string()
+                    }
+                    QuerySortKey::Temp => "temp_blks_written".to_string(),
+                    QuerySortKey::Wal if has_exec_time => "wal_bytes".to_string(),
+                    QuerySortKey::Wal => {
+                        return Err(
+                            "Sorting by WAL requires pg_stat_statements 1.8 (PostgreSQL 13) or newer"
+                                .to_string(),
+                        )
+                    }
+                };
+                format!("ORDER BY {} DESC NULLS LAST LIMIT $1", key)
+            }
+        };
+
+        Ok(format!(
+            r#"
             SELECT
+                queryid,
+                userid,
+                dbid,
                 query,
                 calls,
-                total_exec_time,
-                mean_exec_time,
-                min_exec_time,
-                max_exec_time,
+                {total},
+                {mean},
+                {min},
+                {max},
                 rows,
                 shared_blks_hit,
-                shared_blks_read
+                shared_blks_read,
+                local_blks_read,
+                temp_blks_read,
+                temp_blks_written,
+                {plans},
+                {plan_time},
+                {wal_bytes},
+                {jit}
             FROM pg_stat_statements
-            ORDER BY total_exec_time DESC
-            LIMIT $1
-        "#;
-
-        let rows = client
-            .query(query, &[&(limit as i64)])
-            .await
-            .map_err(|e| format!("Failed to query pg_stat_statements: {}", e))?;
-
-        let stats = rows
-            .iter()
-            .map(|row| QueryStat {
-                query: row.get(0),
-                calls: row.get(1),
-                total_exec_time: row.get(2),
-                mean_exec_time: row.get(3),
-                min_exec_time: row.get(4),
-                max_exec_time: row.get(5),
-                rows: row.get(6),
-                shared_blks_hit: row.get(7),
-                shared_blks_read: row.get(8),
-            })
-            .collect();
+            {order}
+            "#
+        ))
+    }
 
-        Ok(stats)
+    pub fn query_stat_from_row(row: &tokio_postgres::Row) -> QueryStat {
+        QueryStat {
+            queryid: row.get(0),
+            userid: row.get(1),
+            dbid: row.get(2),
+            query: row.get::<_, Option<String>>(3).unwrap_or_default(),
+            calls: row.get(4),
+            total_exec_time: row.get(5),
+            mean_exec_time: row.get(6),
+            min_exec_time: row.get(7),
+            max_exec_time: row.get(8),
+            rows: row.get(9),
+            shared_blks_hit: row.get(10),
+            shared_blks_read: row.get(11),
+            local_blks_read: row.get(12),
+            temp_blks_read: row.get(13),
+            temp_blks_written: row.get(14),
+            plans: row.get(15),
+            total_plan_time: row.get(16),
+            wal_bytes: row.get(17),
+            jit_functions: row.get(18),
+            jit_generation_time: row.get(19),
+            jit_inlining_time: row.get(20),
+            jit_optimization_time: row.get(21),
+            jit_emission_time: row.get(22),
+        }
     }
 
     pub async fn get_active_queries(
@@ -302,24 +398,11 @@ impl MetricsCollector {
         manager: &ConnectionManager,
         server_id: &str,
     ) -> Result<BgWriterStats, String> {
+        let capabilities = manager.capabilities(server_id).await?;
         let client = manager.get_client(server_id).await?;
 
-        let query = r#"
-            SELECT
-                checkpoints_timed,
-                checkpoints_req,
-                checkpoint_write_time,
-                checkpoint_sync_time,
-                buffers_checkpoint,
-                buffers_clean,
-                maxwritten_clean,
-                buffers_backend,
-                buffers_alloc
-            FROM pg_stat_bgwriter
-        "#;
-
         let row = client
-            .query_one(query, &[])
+            .query_one(Self::bgwriter_query(&capabilities), &[])
             .await
             .map_err(|e| format!("Failed to query bgwriter stats: {}", e))?;
 
@@ -333,6 +416,316 @@ impl MetricsCollector {
             maxwritten_clean: row.get(6),
             buffers_backend: row.get(7),
             buffers_alloc: row.get(8),
+            stats_reset: row.get(9),
+            restartpoints_timed: row.get(10),
+            restartpoints_req: row.get(11),
+            restartpoints_done: row.get(12),
+            checkpointer_stats_reset: row.get(13),
+        })
+    }
+
+    /// Pick the checkpointer/bgwriter query for a server version. Every
+    /// variant returns the same columns in the same order.
+    pub fn bgwriter_query(capabilities: &ServerCapabilities) -> &'static str {
+        if capabilities.has_stat_checkpointer() {
+            r#"
+                SELECT
+                    c.num_timed,
+                    c.num_requested,
+                    c.write_time,
+                    c.sync_time,
+                    c.buffers_written,
+                    b.buffers_clean,
+                    b.maxwritten_clean,
+                    (
+                        SELECT sum(writes)::bigint
+                        FROM pg_stat_io
+                        WHERE object = 'relation'
+                        AND backend_type NOT IN ('checkpointer', 'background writer')
+                    ),
+                    b.buffers_alloc,
+                    b.stats_reset::text,
+                    c.restartpoints_timed,
+                    c.restartpoints_req,
+                    c.restartpoints_done,
+                    c.stats_reset::text
+                FROM pg_stat_checkpointer c, pg_stat_bgwriter b
+            "#
+        } else if capabilities.has_stat_io() {
+            // PG16 still has buffers_backend, but pg_stat_io also counts
+            // writes by autovacuum and other background workers
+            r#"
+                SELECT
+                    checkpoints_timed,
+                    checkpoints_req,
+                    checkpoint_write_time,
+                    checkpoint_sync_time,
+                    buffers_checkpoint,
+                    buffers_clean,
+                    maxwritten_clean,
+                    (
+                        SELECT sum(writes)::bigint
+                        FROM pg_stat_io
+                        WHERE object = 'relation'
+                        AND backend_type NOT IN ('checkpointer', 'background writer')
+                    ),
+                    buffers_alloc,
+                    stats_reset::text,
+                    NULL::bigint,
+                    NULL::bigint,
+                    NULL::bigint,
+                    NULL::text
+                FROM pg_stat_bgwriter
+            "#
+        } else {
+            r#"
+                SELECT
+                    checkpoints_timed,
+                    checkpoints_req,
+                    checkpoint_write_time,
+                    checkpoint_sync_time,
+                    buffers_checkpoint,
+                    buffers_clean,
+                    maxwritten_clean,
+                    buffers_backend,
+                    buffers_alloc,
+                    stats_reset::text,
+                    NULL::bigint,
+                    NULL::bigint,
+                    NULL::bigint,
+                    NULL::text
+                FROM pg_stat_bgwriter
+            "#
+        }
+    }
+
+    pub async fn get_replication_stats(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<Vec<ReplicationStat>, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let client = manager.get_client(server_id).await?;
+
+        let reply_time = if capabilities.version_num >= 120000 {
+            "reply_time::text"
+        } else {
+            "NULL::text"
+        };
+
+        // A cascading standby has no current WAL position of its own
+        let query = format!(
+            r#"
+            SELECT
+                pid,
+                usename::text,
+                application_name,
+                client_addr::text,
+                state,
+                sync_state,
+                sync_priority,
+                sent_lsn::text,
+                write_lsn::text,
+                flush_lsn::text,
+                replay_lsn::text,
+                EXTRACT(EPOCH FROM write_lag)::float8,
+                EXTRACT(EPOCH FROM flush_lag)::float8,
+                EXTRACT(EPOCH FROM replay_lag)::float8,
+                pg_wal_lsn_diff(
+                    CASE WHEN pg_is_in_recovery() THEN pg_last_wal_receive_lsn()
+                         ELSE pg_current_wal_lsn() END,
+                    replay_lsn
+                )::bigint,
+                backend_start::text,
+                {reply_time}
+            FROM pg_stat_replication
+            ORDER BY application_name, pid
+            "#
+        );
+
+        let rows = client
+            .query(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to query replication stats: {}", e))?;
+
+        let stats = rows
+            .iter()
+            .map(|row| ReplicationStat {
+                pid: row.get(0),
+                usename: row.get(1),
+                application_name: row.get(2),
+                client_addr: row.get(3),
+                state: row.get(4),
+                sync_state: row.get(5),
+                sync_priority: row.get(6),
+                sent_lsn: row.get(7),
+                write_lsn: row.get(8),
+                flush_lsn: row.get(9),
+                replay_lsn: row.get(10),
+                write_lag_secs: row.get(11),
+                flush_lag_secs: row.get(12),
+                replay_lag_secs: row.get(13),
+                replay_lag_bytes: row.get(14),
+                backend_start: row.get(15),
+                reply_time: row.get(16),
+            })
+            .collect();
+
+        Ok(stats)
+    }
+
+    pub async fn get_replication_slots(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<Vec<ReplicationSlot>, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let client = manager.get_client(server_id).await?;
+
+        let (wal_status, safe_wal_size) = if capabilities.version_num >= 130000 {
+            ("wal_status", "safe_wal_size")
+        } else {
+            ("NULL::text", "NULL::bigint")
+        };
+
+        let query = format!(
+            r#"
+            SELECT
+                slot_name::text,
+                plugin::text,
+                slot_type,
+                database::text,
+                active,
+                active_pid,
+                temporary,
+                restart_lsn::text,
+                confirmed_flush_lsn::text,
+                pg_wal_lsn_diff(
+                    CASE WHEN pg_is_in_recovery() THEN pg_last_wal_receive_lsn()
+                         ELSE pg_current_wal_lsn() END,
+                    restart_lsn
+                )::bigint,
+                {wal_status},
+                {safe_wal_size}
+            FROM pg_replication_slots
+            ORDER BY slot_name
+            "#
+        );
+
+        let rows = client
+            .query(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to query replication slots: {}", e))?;
+
+        let slots = rows
+            .iter()
+            .map(|row| ReplicationSlot {
+                slot_name: row.get(0),
+                plugin: row.get(1),
+                slot_type: row.get(2),
+                database: row.get(3),
+                active: row.get(4),
+                active_pid: row.get(5),
+                temporary: row.get(6),
+                restart_lsn: row.get(7),
+                confirmed_flush_lsn: row.get(8),
+                retained_wal_bytes: row.get(9),
+                wal_status: row.get(10),
+                safe_wal_size: row.get(11),
+            })
+            .collect();
+
+        Ok(slots)
+    }
+
+    /// WAL receiver status; None on a primary or when streaming is down
+    pub async fn get_wal_receiver(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<Option<WalReceiverStat>, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let client = manager.get_client(server_id).await?;
+
+        let flushed_lsn = if capabilities.version_num >= 130000 {
+            "flushed_lsn::text"
+        } else {
+            "received_lsn::text"
+        };
+        // sender_host/sender_port exist since PG11
+        let (sender_host, sender_port) = if capabilities.version_num >= 110000 {
+            ("sender_host", "sender_port")
+        } else {
+            ("NULL::text", "NULL::int")
+        };
+
+        let query = format!(
+            r#"
+            SELECT
+                pid,
+                status,
+                {sender_host},
+                {sender_port},
+                slot_name,
+                {flushed_lsn},
+                latest_end_lsn::text,
+                latest_end_time::text,
+                last_msg_send_time::text,
+                last_msg_receipt_time::text
+            FROM pg_stat_wal_receiver
+            "#
+        );
+
+        let row = client
+            .query_opt(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to query WAL receiver: {}", e))?;
+
+        Ok(row.map(|row| WalReceiverStat {
+            pid: row.get(0),
+            status: row.get(1),
+            sender_host: row.get(2),
+            sender_port: row.get(3),
+            slot_name: row.get(4),
+            flushed_lsn: row.get(5),
+            latest_end_lsn: row.get(6),
+            latest_end_time: row.get(7),
+            last_msg_send_time: row.get(8),
+            last_msg_receipt_time: row.get(9),
+        }))
+    }
+
+    pub async fn get_recovery_status(
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<RecoveryStatus, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let query = r#"
+            SELECT
+                pg_is_in_recovery(),
+                CASE WHEN pg_is_in_recovery() THEN pg_is_wal_replay_paused() END,
+                pg_last_wal_receive_lsn()::text,
+                pg_last_wal_replay_lsn()::text,
+                pg_wal_lsn_diff(pg_last_wal_receive_lsn(), pg_last_wal_replay_lsn())::bigint,
+                pg_last_xact_replay_timestamp()::text,
+                CASE
+                    WHEN NOT pg_is_in_recovery() THEN NULL
+                    WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+                    ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())::float8
+                END
+        "#;
+
+        let row = client
+            .query_one(query, &[])
+            .await
+            .map_err(|e| format!("Failed to query recovery status: {}", e))?;
+
+        Ok(RecoveryStatus {
+            in_recovery: row.get(0),
+            replay_paused: row.get(1),
+            last_receive_lsn: row.get(2),
+            last_replay_lsn: row.get(3),
+            replay_lag_bytes: row.get(4),
+            last_replay_timestamp: row.get(5),
+            replay_lag_secs: row.get(6),
         })
     }
 
@@ -380,16 +773,7 @@ impl MetricsCollector {
     ) -> Result<ExplainPlan, String> {
         let client = manager.get_client(server_id).await?;
 
-        // Validate query - only allow SELECT, INSERT, UPDATE, DELETE, WITH
-        let trimmed = query.trim().to_uppercase();
-        if !trimmed.starts_with("SELECT")
-            && !trimmed.starts_with("INSERT")
-            && !trimmed.starts_with("UPDATE")
-            && !trimmed.starts_with("DELETE")
-            && !trimmed.starts_with("WITH")
-        {
-            return Err("Only SELECT, INSERT, UPDATE, DELETE, or WITH queries can be explained".to_string());
-        }
+        Self::check_explainable(query)?;
 
         // Build EXPLAIN command
         let explain_query = if analyze {
@@ -404,8 +788,25 @@ impl MetricsCollector {
             .map_err(|e| format!("Failed to explain query: {}", e))?;
 
         let json_plan: Value = row.get(0);
+        Self::parse_explain_output(query, &json_plan)
+    }
+
+    /// Validate query - only allow SELECT, INSERT, UPDATE, DELETE, WITH
+    pub fn check_explainable(query: &str) -> Result<(), String> {
+        let trimmed = query.trim().to_uppercase();
+        if !trimmed.starts_with("SELECT")
+            && !trimmed.starts_with("INSERT")
+            && !trimmed.starts_with("UPDATE")
+            && !trimmed.starts_with("DELETE")
+            && !trimmed.starts_with("WITH")
+        {
+            return Err("Only SELECT, INSERT, UPDATE, DELETE, or WITH queries can be explained".to_string());
+        }
+        Ok(())
+    }
 
-        // Parse the JSON plan
+    /// Parse the output of EXPLAIN (FORMAT JSON)
+    pub fn parse_explain_output(query: &str, json_plan: &Value) -> Result<ExplainPlan, String> {
         let plan_array = json_plan
             .as_array()
             .and_then(|arr| arr.first())
@@ -442,6 +843,7 @@ impl MetricsCollector {
             .to_string();
 
         let relation_name = obj.get("Relation Name").and_then(|v| v.as_str()).map(String::from);
+        let schema = obj.get("Schema").and_then(|v| v.as_str()).map(String::from);
         let alias = obj.get("Alias").and_then(|v| v.as_str()).map(String::from);
 
         let startup_cost = obj.get("Startup Cost").and_then(|v| v.as_f64()).unwrap_or(0.0);
@@ -524,6 +926,7 @@ impl MetricsCollector {
         Ok(ExplainNode {
             node_type,
             relation_name,
+            schema,
             alias,
             startup_cost,
             total_cost,
@@ -555,3 +958,41 @@ impl MetricsCollector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bgwriter_query(version_num: i32) -> &'static str {
+        MetricsCollector::bgwriter_query(&ServerCapabilities::new(version_num))
+    }
+
+    #[test]
+    fn bgwriter_query_before_pg16_reads_pg_stat_bgwriter_only() {
+        for version_num in [120000, 130000, 140000, 150000] {
+            let query = bgwriter_query(version_num);
+            assert!(query.contains("FROM pg_stat_bgwriter"), "{}", version_num);
+            assert!(query.contains("buffers_backend,"), "{}", version_num);
+            assert!(!query.contains("pg_stat_io"), "{}", version_num);
+            assert!(!query.contains("pg_stat_checkpointer"), "{}", version_num);
+        }
+    }
+
+    #[test]
+    fn bgwriter_query_on_pg16_counts_backend_writes_from_pg_stat_io() {
+        let query = bgwriter_query(160000);
+        assert!(query.contains("FROM pg_stat_bgwriter"));
+        assert!(query.contains("FROM pg_stat_io"));
+        assert!(!query.contains("buffers_backend,"));
+        assert!(!query.contains("pg_stat_checkpointer"));
+    }
+
+    #[test]
+    fn bgwriter_query_from_pg17_reads_pg_stat_checkpointer() {
+        let query = bgwriter_query(170000);
+        assert!(query.contains("FROM pg_stat_checkpointer c, pg_stat_bgwriter b"));
+        assert!(query.contains("FROM pg_stat_io"));
+        assert!(query.contains("c.restartpoints_done"));
+        assert!(!query.contains("checkpoints_timed"));
+    }
+}
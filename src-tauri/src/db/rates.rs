@@ -0,0 +1,289 @@
+use super::connection::ConnectionManager;
+use super::metrics::{BgWriterStats, DatabaseStats};
+use super::queries::MetricsCollector;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Per-second rates for one database over the last sampling interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseRates {
+    pub datname: String,
+    pub numbackends: i32,
+    /// Commits + rollbacks per second
+    pub tps: f64,
+    pub commits_per_sec: f64,
+    pub rollbacks_per_sec: f64,
+    /// Share of transactions that rolled back in the interval (0-100)
+    pub rollback_ratio: Option<f64>,
+    /// Cache hit ratio of block accesses in the interval (0-100)
+    pub cache_hit_ratio: Option<f64>,
+    pub blks_read_per_sec: f64,
+    pub blks_hit_per_sec: f64,
+    pub tup_returned_per_sec: f64,
+    pub tup_fetched_per_sec: f64,
+    pub tup_inserted_per_sec: f64,
+    pub tup_updated_per_sec: f64,
+    pub tup_deleted_per_sec: f64,
+    pub temp_files_per_sec: f64,
+    pub temp_bytes_per_sec: f64,
+    pub deadlocks_per_sec: f64,
+    pub conflicts_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BgWriterRates {
+    pub checkpoints_timed_per_sec: f64,
+    pub checkpoints_req_per_sec: f64,
+    /// Milliseconds spent writing/syncing checkpoints per second
+    pub checkpoint_write_time_per_sec: f64,
+    pub checkpoint_sync_time_per_sec: f64,
+    pub buffers_checkpoint_per_sec: f64,
+    pub buffers_clean_per_sec: f64,
+    pub maxwritten_clean_per_sec: f64,
+    /// Unavailable when the server does not expose backend writes
+    pub buffers_backend_per_sec: Option<f64>,
+    pub buffers_alloc_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerRates {
+    /// Seconds since the previous sample; None on the first call (baseline)
+    pub interval_secs: Option<f64>,
+    /// Set when the server restarted since the previous sample
+    pub server_restarted: bool,
+    /// Databases whose counters were reset (or went backwards) and have no
+    /// rates for this interval
+    pub reset_databases: Vec<String>,
+    pub bgwriter_reset: bool,
+    pub databases: Vec<DatabaseRates>,
+    /// Sum over all databases with valid rates
+    pub total: Option<DatabaseRates>,
+    pub bgwriter: Option<BgWriterRates>,
+}
+
+struct CounterSample {
+    taken_at: Instant,
+    postmaster_start: String,
+    databases: HashMap<String, DatabaseStats>,
+    bgwriter: BgWriterStats,
+}
+
+/// Turns cumulative pg_stat counters into per-second rates by keeping the
+/// previous sample of each server
+#[derive(Clone, Default)]
+pub struct RateEngine {
+    previous: Arc<Mutex<HashMap<String, CounterSample>>>,
+}
+
+impl RateEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get_rates(
+        &self,
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<ServerRates, String> {
+        let postmaster_start = {
+            let client = manager.get_client(server_id).await?;
+            let row = client
+                .query_one("SELECT pg_postmaster_start_time()::text", &[])
+                .await
+                .map_err(|e| format!("Failed to query postmaster start time: {}", e))?;
+            row.get::<_, String>(0)
+        };
+
+        let databases = MetricsCollector::get_database_stats(manager, server_id).await?;
+        let bgwriter = MetricsCollector::get_bgwriter_stats(manager, server_id).await?;
+
+        let current = CounterSample {
+            taken_at: Instant::now(),
+            postmaster_start,
+            databases: databases
+                .into_iter()
+                .map(|db| (db.datname.clone(), db))
+                .collect(),
+            bgwriter,
+        };
+
+        let mut previous = self.previous.lock().unwrap();
+
+        let rates = match previous.get(server_id) {
+            Some(prev) => Self::compare(prev, &current),
+            None => ServerRates {
+                interval_secs: None,
+                server_restarted: false,
+                reset_databases: Vec::new(),
+                bgwriter_reset: false,
+                databases: Vec::new(),
+                total: None,
+                bgwriter: None,
+            },
+        };
+
+        previous.insert(server_id.to_string(), current);
+
+        Ok(rates)
+    }
+
+    /// Drop the stored sample of a server
+    pub fn forget(&self, server_id: &str) {
+        self.previous.lock().unwrap().remove(server_id);
+    }
+
+    fn compare(previous: &CounterSample, current: &CounterSample) -> ServerRates {
+        let secs = current
+            .taken_at
+            .duration_since(previous.taken_at)
+            .as_secs_f64();
+
+        let server_restarted = previous.postmaster_start != current.postmaster_start;
+
+        if server_restarted || secs <= 0.0 {
+            return ServerRates {
+                interval_secs: Some(secs),
+                server_restarted,
+                reset_databases: current.databases.keys().cloned().collect(),
+                bgwriter_reset: true,
+                databases: Vec::new(),
+                total: None,
+                bgwriter: None,
+            };
+        }
+
+        let mut databases = Vec::new();
+        let mut reset_databases = Vec::new();
+
+        let mut names: Vec<&String> = current.databases.keys().collect();
+        names.sort();
+
+        for name in names {
+            let cur = &current.databases[name];
+            // New databases start their own baseline
+            let Some(prev) = previous.databases.get(name) else {
+                continue;
+            };
+            match database_rates(prev, cur, secs) {
+                Some(rates) => databases.push(rates),
+                None => reset_databases.push(name.clone()),
+            }
+        }
+
+        let bgwriter = bgwriter_rates(&previous.bgwriter, &current.bgwriter, secs);
+
+        ServerRates {
+            interval_secs: Some(secs),
+            server_restarted,
+            reset_databases,
+            bgwriter_reset: bgwriter.is_none(),
+            total: total_rates(&databases),
+            databases,
+            bgwriter,
+        }
+    }
+}
+
+/// Counter delta; None if the counter went backwards (reset)
+fn delta(current: i64, previous: i64) -> Option<f64> {
+    (current >= previous).then(|| (current - previous) as f64)
+}
+
+fn delta_f(current: f64, previous: f64) -> Option<f64> {
+    (current >= previous).then_some(current - previous)
+}
+
+fn ratio(part: f64, whole: f64) -> Option<f64> {
+    (whole > 0.0).then(|| part / whole * 100.0)
+}
+
+fn database_rates(prev: &DatabaseStats, cur: &DatabaseStats, secs: f64) -> Option<DatabaseRates> {
+    if prev.stats_reset != cur.stats_reset {
+        return None;
+    }
+
+    let commits = delta(cur.xact_commit, prev.xact_commit)?;
+    let rollbacks = delta(cur.xact_rollback, prev.xact_rollback)?;
+    let blks_read = delta(cur.blks_read, prev.blks_read)?;
+    let blks_hit = delta(cur.blks_hit, prev.blks_hit)?;
+
+    Some(DatabaseRates {
+        datname: cur.datname.clone(),
+        numbackends: cur.numbackends,
+        tps: (commits + rollbacks) / secs,
+        commits_per_sec: commits / secs,
+        rollbacks_per_sec: rollbacks / secs,
+        rollback_ratio: ratio(rollbacks, commits + rollbacks),
+        cache_hit_ratio: ratio(blks_hit, blks_hit + blks_read),
+        blks_read_per_sec: blks_read / secs,
+        blks_hit_per_sec: blks_hit / secs,
+        tup_returned_per_sec: delta(cur.tup_returned, prev.tup_returned)? / secs,
+        tup_fetched_per_sec: delta(cur.tup_fetched, prev.tup_fetched)? / secs,
+        tup_inserted_per_sec: delta(cur.tup_inserted, prev.tup_inserted)? / secs,
+        tup_updated_per_sec: delta(cur.tup_updated, prev.tup_updated)? / secs,
+        tup_deleted_per_sec: delta(cur.tup_deleted, prev.tup_deleted)? / secs,
+        temp_files_per_sec: delta(cur.temp_files, prev.temp_files)? / secs,
+        temp_bytes_per_sec: delta(cur.temp_bytes, prev.temp_bytes)? / secs,
+        deadlocks_per_sec: delta(cur.deadlocks, prev.deadlocks)? / secs,
+        conflicts_per_sec: delta(cur.conflicts, prev.conflicts)? / secs,
+    })
+}
+
+fn bgwriter_rates(prev: &BgWriterStats, cur: &BgWriterStats, secs: f64) -> Option<BgWriterRates> {
+    if prev.stats_reset != cur.stats_reset
+        || prev.checkpointer_stats_reset != cur.checkpointer_stats_reset
+    {
+        return None;
+    }
+
+    Some(BgWriterRates {
+        checkpoints_timed_per_sec: delta(cur.checkpoints_timed, prev.checkpoints_timed)? / secs,
+        checkpoints_req_per_sec: delta(cur.checkpoints_req, prev.checkpoints_req)? / secs,
+        checkpoint_write_time_per_sec: delta_f(cur.checkpoint_write_time, prev.checkpoint_write_time)? / secs,
+        checkpoint_sync_time_per_sec: delta_f(cur.checkpoint_sync_time, prev.checkpoint_sync_time)? / secs,
+        buffers_checkpoint_per_sec: delta(cur.buffers_checkpoint, prev.buffers_checkpoint)? / secs,
+        buffers_clean_per_sec: delta(cur.buffers_clean, prev.buffers_clean)? / secs,
+        maxwritten_clean_per_sec: delta(cur.maxwritten_clean, prev.maxwritten_clean)? / secs,
+        buffers_backend_per_sec: match (cur.buffers_backend, prev.buffers_backend) {
+            (Some(cur), Some(prev)) => Some(delta(cur, prev)? / secs),
+            _ => None,
+        },
+        buffers_alloc_per_sec: delta(cur.buffers_alloc, prev.buffers_alloc)? / secs,
+    })
+}
+
+fn total_rates(databases: &[DatabaseRates]) -> Option<DatabaseRates> {
+    if databases.is_empty() {
+        return None;
+    }
+
+    let sum = |f: fn(&DatabaseRates) -> f64| databases.iter().map(f).sum::<f64>();
+
+    let commits = sum(|d| d.commits_per_sec);
+    let rollbacks = sum(|d| d.rollbacks_per_sec);
+    let blks_read = sum(|d| d.blks_read_per_sec);
+    let blks_hit = sum(|d| d.blks_hit_per_sec);
+
+    Some(DatabaseRates {
+        datname: "*".to_string(),
+        numbackends: databases.iter().map(|d| d.numbackends).sum(),
+        tps: commits + rollbacks,
+        commits_per_sec: commits,
+        rollbacks_per_sec: rollbacks,
+        rollback_ratio: ratio(rollbacks, commits + rollbacks),
+        cache_hit_ratio: ratio(blks_hit, blks_hit + blks_read),
+        blks_read_per_sec: blks_read,
+        blks_hit_per_sec: blks_hit,
+        tup_returned_per_sec: sum(|d| d.tup_returned_per_sec),
+        tup_fetched_per_sec: sum(|d| d.tup_fetched_per_sec),
+        tup_inserted_per_sec: sum(|d| d.tup_inserted_per_sec),
+        tup_updated_per_sec: sum(|d| d.tup_updated_per_sec),
+        tup_deleted_per_sec: sum(|d| d.tup_deleted_per_sec),
+        temp_files_per_sec: sum(|d| d.temp_files_per_sec),
+        temp_bytes_per_sec: sum(|d| d.temp_bytes_per_sec),
+        deadlocks_per_sec: sum(|d| d.deadlocks_per_sec),
+        conflicts_per_sec: sum(|d| d.conflicts_per_sec),
+    })
+}
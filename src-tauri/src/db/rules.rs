@@ -0,0 +1,1054 @@
+use super::analyzer::{ConfigIssue, IssueSeverity, PerformanceIssue};
+use super::analyzer_config::{
+    AnalyzerConfigFile, AnalyzerConfigStatus, DeclarativeRule, Thresholds,
+};
+use super::bloat::{BloatCollector, RelationBloat};
+use super::config::{ConfigCollector, HardwareInfo};
+use super::connection::ConnectionManager;
+use super::indexes::{IndexAdvisor, IndexReport};
+use super::settings::{format_bytes, ServerSettings};
+use super::storage;
+use super::wraparound::{WraparoundAnalyzer, WraparoundReport, XidRateTracker};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const MB: i64 = 1024 * 1024;
+/// Relations checked for bloat
+const BLOAT_LIMIT: i32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleCategory {
+    Memory,
+    Connections,
+    Cache,
+    Queries,
+    Indexes,
+    Maintenance,
+    /// Declarative rules from analyzer.toml
+    Custom,
+}
+
+/// Data a rule needs; only inputs required by an enabled rule are collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleInput {
+    Settings,
+    Hardware,
+    CacheStats,
+    TableStats,
+    Indexes,
+    Bloat,
+    Wraparound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    Config,
+    Performance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RuleIssue {
+    Config(ConfigIssue),
+    Performance(PerformanceIssue),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeqScanTable {
+    pub schemaname: String,
+    pub relname: String,
+    pub seq_scan: i64,
+    pub seq_tup_read: i64,
+}
+
+/// Everything rules look at. Inputs that were not requested or failed to
+/// load are None. Serializable so a recorded context can be replayed
+/// against the rules without a database.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleContext {
+    #[serde(default)]
+    pub server_id: String,
+    #[serde(default)]
+    pub server_name: Option<String>,
+    pub version_num: i32,
+    #[serde(default)]
+    pub thresholds: Thresholds,
+    pub settings: Option<ServerSettings>,
+    pub hardware: Option<HardwareInfo>,
+    pub cache_hit_ratio: Option<f64>,
+    pub seq_scan_tables: Option<Vec<SeqScanTable>>,
+    pub index_report: Option<IndexReport>,
+    pub table_bloat: Option<Vec<RelationBloat>>,
+    pub index_bloat: Option<Vec<RelationBloat>>,
+    pub wraparound: Option<WraparoundReport>,
+    /// Rows returned by declarative rule queries, by rule id
+    #[serde(default)]
+    pub custom_rows: HashMap<String, Result<Vec<Value>, String>>,
+}
+
+impl RuleContext {
+    /// Load the given inputs. Settings and hardware errors are returned;
+    /// statistics that fail to load are left out. Wraparound needs the
+    /// XID tracker and is skipped without it.
+    pub async fn collect(
+        manager: &ConnectionManager,
+        server_id: &str,
+        inputs: &HashSet<RuleInput>,
+        thresholds: &Thresholds,
+        xid_tracker: Option<&XidRateTracker>,
+    ) -> Result<RuleContext, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let mut context = RuleContext {
+            server_id: server_id.to_string(),
+            server_name: manager.get_server_info(server_id).map(|info| info.name),
+            version_num: capabilities.version_num,
+            thresholds: thresholds.clone(),
+            ..Default::default()
+        };
+
+        if inputs.contains(&RuleInput::Settings) {
+            context.settings = Some(ConfigCollector::get_typed_settings(manager, server_id).await?);
+        }
+        if inputs.contains(&RuleInput::Hardware) {
+            context.hardware = Some(ConfigCollector::get_hardware_info(manager, server_id).await?);
+        }
+
+        if inputs.contains(&RuleInput::CacheStats) || inputs.contains(&RuleInput::TableStats) {
+            let client = manager.get_client(server_id).await?;
+
+            if inputs.contains(&RuleInput::CacheStats) {
+                let cache_query = r#"
+                    SELECT
+                        sum(blks_hit)::float / NULLIF(sum(blks_hit + blks_read), 0) * 100 as cache_hit_ratio
+                    FROM pg_stat_database
+                "#;
+                if let Ok(row) = client.query_one(cache_query, &[]).await {
+                    context.cache_hit_ratio = row.get(0);
+                }
+            }
+
+            if inputs.contains(&RuleInput::TableStats) {
+                let seq_scan_query = r#"
+                    SELECT schemaname::text, relname::text, seq_scan, seq_tup_read
+                    FROM pg_stat_user_tables
+                    WHERE seq_scan > $1 AND seq_tup_read > $2
+                    ORDER BY seq_tup_read DESC
+                    LIMIT 5
+                "#;
+                if let Ok(rows) = client
+                    .query(
+                        seq_scan_query,
+                        &[&thresholds.seq_scan_min, &thresholds.seq_tup_read_min],
+                    )
+                    .await
+                {
+                    context.seq_scan_tables = Some(
+                        rows.iter()
+                            .map(|row| SeqScanTable {
+                                schemaname: row.get(0),
+                                relname: row.get(1),
+                                seq_scan: row.get(2),
+                                seq_tup_read: row.get(3),
+                            })
+                            .collect(),
+                    );
+                }
+            }
+        }
+
+        if inputs.contains(&RuleInput::Indexes) {
+            context.index_report = IndexAdvisor::get_report(manager, server_id).await.ok();
+        }
+
+        if inputs.contains(&RuleInput::Bloat) {
+            context.table_bloat =
+                BloatCollector::get_table_bloat(manager, server_id, BLOAT_LIMIT, false)
+                    .await
+                    .ok();
+            context.index_bloat =
+                BloatCollector::get_index_bloat(manager, server_id, BLOAT_LIMIT, false)
+                    .await
+                    .ok();
+        }
+
+        if let (true, Some(tracker)) = (inputs.contains(&RuleInput::Wraparound), xid_tracker) {
+            context.wraparound = WraparoundAnalyzer::get_report(manager, server_id, tracker)
+                .await
+                .ok();
+        }
+
+        Ok(context)
+    }
+
+    fn has(&self, input: RuleInput) -> bool {
+        match input {
+            RuleInput::Settings => self.settings.is_some(),
+            RuleInput::Hardware => self.hardware.is_some(),
+            RuleInput::CacheStats => self.cache_hit_ratio.is_some(),
+            RuleInput::TableStats => self.seq_scan_tables.is_some(),
+            RuleInput::Indexes => self.index_report.is_some(),
+            RuleInput::Bloat => self.table_bloat.is_some() && self.index_bloat.is_some(),
+            RuleInput::Wraparound => self.wraparound.is_some(),
+        }
+    }
+}
+
+/// A configuration or performance check
+pub trait Rule: Send + Sync {
+    /// Stable identifier used to enable or disable the rule
+    fn id(&self) -> &str;
+    fn category(&self) -> RuleCategory;
+    fn description(&self) -> &str;
+    fn kind(&self) -> IssueKind;
+    fn inputs(&self) -> &'static [RuleInput];
+
+    /// Lowest server_version_num the rule applies to
+    fn min_version(&self) -> Option<i32> {
+        None
+    }
+
+    /// First server_version_num the rule no longer applies to
+    fn max_version(&self) -> Option<i32> {
+        None
+    }
+
+    /// Called only when every input is present in the context
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue>;
+
+    /// Whether the rule got complete data beyond its inputs; issues of a
+    /// rule that did not are not considered resolved
+    fn has_data(&self, _context: &RuleContext) -> bool {
+        true
+    }
+
+    fn applies_to(&self, version_num: i32) -> bool {
+        self.min_version().is_none_or(|min| version_num >= min)
+            && self.max_version().is_none_or(|max| version_num < max)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleInfo {
+    pub id: String,
+    pub category: RuleCategory,
+    pub description: String,
+    pub kind: IssueKind,
+    pub inputs: Vec<RuleInput>,
+    pub min_version: Option<i32>,
+    pub max_version: Option<i32>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RulesConfig {
+    /// Ids of disabled rules
+    #[serde(default)]
+    pub disabled: Vec<String>,
+}
+
+/// Rules loaded from analyzer.toml along with its thresholds
+#[derive(Default)]
+struct CustomRules {
+    file: AnalyzerConfigFile,
+    rules: Vec<Arc<DeclarativeRule>>,
+    status: AnalyzerConfigStatus,
+}
+
+impl CustomRules {
+    fn load(builtin_ids: &[String]) -> Self {
+        let (file, status) = AnalyzerConfigFile::load(builtin_ids);
+        for error in &status.errors {
+            eprintln!("{}", error);
+        }
+        let rules = file
+            .rules
+            .iter()
+            .filter_map(|spec| DeclarativeRule::new(spec.clone()).ok())
+            .map(Arc::new)
+            .collect();
+        CustomRules {
+            file,
+            rules,
+            status,
+        }
+    }
+}
+
+/// Registered rules and which of them are enabled
+#[derive(Clone)]
+pub struct RuleRegistry {
+    rules: Arc<Vec<Arc<dyn Rule>>>,
+    custom: Arc<Mutex<CustomRules>>,
+    config: Arc<Mutex<RulesConfig>>,
+}
+
+impl RuleRegistry {
+    /// Built-in rules and analyzer.toml with the saved enable/disable state
+    pub fn new() -> Self {
+        let registry = Self::with_rules(builtin_rules());
+        registry.reload();
+        *registry.config.lock().unwrap() = load_config();
+        registry
+    }
+
+    /// Given rules, all enabled and without analyzer.toml; for replaying
+    /// recorded contexts
+    pub fn with_rules(rules: Vec<Box<dyn Rule>>) -> Self {
+        RuleRegistry {
+            rules: Arc::new(rules.into_iter().map(Arc::from).collect()),
+            custom: Arc::new(Mutex::new(CustomRules::default())),
+            config: Arc::new(Mutex::new(RulesConfig::default())),
+        }
+    }
+
+    /// The same registry without the given built-in rules, so neither they
+    /// run nor their inputs are collected
+    pub fn without(&self, rule_ids: &[&str]) -> Self {
+        RuleRegistry {
+            rules: Arc::new(
+                self.rules
+                    .iter()
+                    .filter(|rule| !rule_ids.contains(&rule.id()))
+                    .cloned()
+                    .collect(),
+            ),
+            custom: self.custom.clone(),
+            config: self.config.clone(),
+        }
+    }
+
+    /// Re-read analyzer.toml, replacing its thresholds and rules
+    pub fn reload(&self) -> AnalyzerConfigStatus {
+        let builtin_ids: Vec<String> = self.rules.iter().map(|r| r.id().to_string()).collect();
+        let custom = CustomRules::load(&builtin_ids);
+        let status = custom.status.clone();
+        *self.custom.lock().unwrap() = custom;
+        status
+    }
+
+    /// Outcome of the last analyzer.toml load
+    pub fn config_status(&self) -> AnalyzerConfigStatus {
+        self.custom.lock().unwrap().status.clone()
+    }
+
+    /// Thresholds for a server after applying analyzer.toml
+    pub fn thresholds_for(&self, server_id: &str, server_name: Option<&str>) -> Thresholds {
+        self.custom
+            .lock()
+            .unwrap()
+            .file
+            .thresholds_for(server_id, server_name)
+    }
+
+    fn all_rules(&self) -> Vec<Arc<dyn Rule>> {
+        let custom = self.custom.lock().unwrap();
+        self.rules
+            .iter()
+            .cloned()
+            .chain(
+                custom
+                    .rules
+                    .iter()
+                    .map(|rule| rule.clone() as Arc<dyn Rule>),
+            )
+            .collect()
+    }
+
+    pub fn list(&self) -> Vec<RuleInfo> {
+        let config = self.config.lock().unwrap().clone();
+        self.all_rules()
+            .iter()
+            .map(|rule| RuleInfo {
+                id: rule.id().to_string(),
+                category: rule.category(),
+                description: rule.description().to_string(),
+                kind: rule.kind(),
+                inputs: rule.inputs().to_vec(),
+                min_version: rule.min_version(),
+                max_version: rule.max_version(),
+                enabled: !config.disabled.iter().any(|id| id == rule.id()),
+            })
+            .collect()
+    }
+
+    pub fn set_enabled(&self, rule_id: &str, enabled: bool) -> Result<(), String> {
+        if !self.all_rules().iter().any(|rule| rule.id() == rule_id) {
+            return Err(format!("Unknown rule: {}", rule_id));
+        }
+
+        let mut config = self.config.lock().unwrap().clone();
+        config.disabled.retain(|id| id != rule_id);
+        if !enabled {
+            config.disabled.push(rule_id.to_string());
+        }
+
+        save_config(&config)?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    fn is_enabled(&self, rule_id: &str) -> bool {
+        let config = self.config.lock().unwrap();
+        !config.disabled.iter().any(|id| id == rule_id)
+    }
+
+    /// Enabled built-in rules of one kind for the server's version
+    fn active(&self, version_num: i32, kind: IssueKind) -> Vec<Arc<dyn Rule>> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.kind() == kind && rule.applies_to(version_num))
+            .filter(|rule| self.is_enabled(rule.id()))
+            .cloned()
+            .collect()
+    }
+
+    /// Enabled declarative rules of one kind scoped to the server
+    fn active_custom(
+        &self,
+        kind: IssueKind,
+        server_id: &str,
+        server_name: Option<&str>,
+    ) -> Vec<Arc<DeclarativeRule>> {
+        let custom = self.custom.lock().unwrap();
+        custom
+            .rules
+            .iter()
+            .filter(|rule| rule.kind() == kind && self.is_enabled(rule.id()))
+            .filter(|rule| {
+                custom
+                    .file
+                    .rule_applies(rule.spec(), server_id, server_name)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Inputs needed by the enabled rules of one kind
+    pub fn required_inputs(&self, version_num: i32, kind: IssueKind) -> HashSet<RuleInput> {
+        self.active(version_num, kind)
+            .iter()
+            .flat_map(|rule| rule.inputs().iter().copied())
+            .collect()
+    }
+
+    /// Collect what the enabled rules of one kind need, including the
+    /// rows of declarative rules, with the server's thresholds applied
+    pub async fn collect_context(
+        &self,
+        manager: &ConnectionManager,
+        server_id: &str,
+        kind: IssueKind,
+        xid_tracker: Option<&XidRateTracker>,
+    ) -> Result<RuleContext, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let server_name = manager.get_server_info(server_id).map(|info| info.name);
+        let thresholds = self.thresholds_for(server_id, server_name.as_deref());
+        let inputs = self.required_inputs(capabilities.version_num, kind);
+
+        let mut context =
+            RuleContext::collect(manager, server_id, &inputs, &thresholds, xid_tracker).await?;
+
+        for rule in self.active_custom(kind, server_id, server_name.as_deref()) {
+            let rows = rule.spec().fetch_rows(manager, server_id).await;
+            context.custom_rows.insert(rule.id().to_string(), rows);
+        }
+
+        Ok(context)
+    }
+
+    /// Run the enabled rules of one kind whose inputs are all present,
+    /// keeping which rule raised each issue and which rules ran
+    pub fn run(&self, context: &RuleContext, kind: IssueKind) -> RuleRun {
+        let custom = self
+            .active_custom(kind, &context.server_id, context.server_name.as_deref())
+            .into_iter()
+            .map(|rule| rule as Arc<dyn Rule>);
+
+        let mut run = RuleRun::default();
+        for rule in self
+            .active(context.version_num, kind)
+            .into_iter()
+            .chain(custom)
+        {
+            if !rule.inputs().iter().all(|input| context.has(*input)) {
+                continue;
+            }
+            if rule.has_data(context) {
+                run.evaluated.insert(rule.id().to_string());
+            }
+            run.issues.extend(
+                rule.evaluate(context)
+                    .into_iter()
+                    .map(|issue| (rule.id().to_string(), issue)),
+            );
+        }
+        run
+    }
+}
+
+/// Result of one evaluation
+#[derive(Debug, Clone, Default)]
+pub struct RuleRun {
+    /// Rules that ran with complete data
+    pub evaluated: HashSet<String>,
+    /// Issues with the id of the rule that raised them
+    pub issues: Vec<(String, RuleIssue)>,
+}
+
+impl Default for RuleRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn builtin_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(SharedBuffersRule),
+        Box::new(EffectiveCacheSizeRule),
+        Box::new(WorkMemRule),
+        Box::new(MaxConnectionsRule),
+        Box::new(CacheHitRatioRule),
+        Box::new(SequentialScanRule),
+        Box::new(IndexHealthRule),
+        Box::new(BloatRule),
+        Box::new(WraparoundRule),
+    ]
+}
+
+pub struct SharedBuffersRule;
+
+impl Rule for SharedBuffersRule {
+    fn id(&self) -> &str {
+        "shared_buffers"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Memory
+    }
+    fn description(&self) -> &str {
+        "shared_buffers below half of 25% of RAM (max 8GB) by default"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Config
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::Settings, RuleInput::Hardware]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        let (Some(settings), Some(hardware)) = (&context.settings, &context.hardware) else {
+            return Vec::new();
+        };
+        let mut issues = Vec::new();
+
+        if let Some(current) = settings.bytes("shared_buffers") {
+            let thresholds = &context.thresholds;
+            let recommended = (hardware.total_memory_mb as f64
+                * thresholds.shared_buffers_ram_ratio)
+                .min(thresholds.shared_buffers_max_mb as f64) as i64
+                * MB;
+
+            if current < recommended / 2 {
+                issues.push(RuleIssue::Config(ConfigIssue {
+                    parameter: "shared_buffers".to_string(),
+                    current_value: format_bytes(current as f64),
+                    recommended_value: format_bytes(recommended as f64),
+                    severity: IssueSeverity::Warning,
+                    reason: format!(
+                        "shared_buffers is too low. Recommended: {:.0}% of RAM (max {})",
+                        thresholds.shared_buffers_ram_ratio * 100.0,
+                        format_bytes((thresholds.shared_buffers_max_mb * MB) as f64)
+                    ),
+                }));
+            }
+        }
+
+        issues
+    }
+}
+
+pub struct EffectiveCacheSizeRule;
+
+impl Rule for EffectiveCacheSizeRule {
+    fn id(&self) -> &str {
+        "effective_cache_size"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Memory
+    }
+    fn description(&self) -> &str {
+        "effective_cache_size below half of 75% of RAM"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Config
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::Settings, RuleInput::Hardware]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        let (Some(settings), Some(hardware)) = (&context.settings, &context.hardware) else {
+            return Vec::new();
+        };
+        let mut issues = Vec::new();
+
+        if let Some(current) = settings.bytes("effective_cache_size") {
+            let recommended = (hardware.total_memory_mb as f64 * 0.75) as i64 * MB;
+
+            if current < recommended / 2 {
+                issues.push(RuleIssue::Config(ConfigIssue {
+                    parameter: "effective_cache_size".to_string(),
+                    current_value: format_bytes(current as f64),
+                    recommended_value: format_bytes(recommended as f64),
+                    severity: IssueSeverity::Info,
+                    reason: "effective_cache_size is low. Recommended: 50-75% of total RAM"
+                        .to_string(),
+                }));
+            }
+        }
+
+        issues
+    }
+}
+
+pub struct WorkMemRule;
+
+impl Rule for WorkMemRule {
+    fn id(&self) -> &str {
+        "work_mem"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Memory
+    }
+    fn description(&self) -> &str {
+        "work_mem below 4MB by default"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Config
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::Settings]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        let Some(settings) = &context.settings else {
+            return Vec::new();
+        };
+        let mut issues = Vec::new();
+
+        if let Some(current) = settings.bytes("work_mem") {
+            if current < context.thresholds.work_mem_min_mb * MB {
+                issues.push(RuleIssue::Config(ConfigIssue {
+                    parameter: "work_mem".to_string(),
+                    current_value: format_bytes(current as f64),
+                    recommended_value: "10-50MB".to_string(),
+                    severity: IssueSeverity::Info,
+                    reason: "work_mem is very low, may affect sort and hash operations".to_string(),
+                }));
+            }
+        }
+
+        issues
+    }
+}
+
+pub struct MaxConnectionsRule;
+
+impl Rule for MaxConnectionsRule {
+    fn id(&self) -> &str {
+        "max_connections"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Connections
+    }
+    fn description(&self) -> &str {
+        "max_connections above twice 50 per CPU (max 200) by default"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Config
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::Settings, RuleInput::Hardware]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        let (Some(settings), Some(hardware)) = (&context.settings, &context.hardware) else {
+            return Vec::new();
+        };
+        let mut issues = Vec::new();
+
+        if let Some(max_conn) = settings.number("max_connections").map(|v| v as i32) {
+            let thresholds = &context.thresholds;
+            let recommended = (hardware.cpu_cores * thresholds.max_connections_per_core)
+                .min(thresholds.max_connections_cap);
+
+            if max_conn > recommended * 2 {
+                issues.push(RuleIssue::Config(ConfigIssue {
+                    parameter: "max_connections".to_string(),
+                    current_value: max_conn.to_string(),
+                    recommended_value: recommended.to_string(),
+                    severity: IssueSeverity::Warning,
+                    reason: "max_connections is very high, may cause resource exhaustion"
+                        .to_string(),
+                }));
+            }
+        }
+
+        issues
+    }
+}
+
+pub struct CacheHitRatioRule;
+
+impl Rule for CacheHitRatioRule {
+    fn id(&self) -> &str {
+        "cache_hit_ratio"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Cache
+    }
+    fn description(&self) -> &str {
+        "Buffer cache hit ratio below 90% by default"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Performance
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::CacheStats]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        let min = context.thresholds.cache_hit_ratio_min;
+        match context.cache_hit_ratio {
+            Some(r) if r < min => vec![RuleIssue::Performance(PerformanceIssue {
+                issue_type: "Low Cache Hit Ratio".to_string(),
+                severity: IssueSeverity::Critical,
+                description: format!("Cache hit ratio is {:.2}%, should be > {}%", r, min),
+                recommendation: "Increase shared_buffers or investigate query patterns".to_string(),
+                details: None,
+                object: None,
+            })],
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub struct SequentialScanRule;
+
+impl Rule for SequentialScanRule {
+    fn id(&self) -> &str {
+        "sequential_scans"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Queries
+    }
+    fn description(&self) -> &str {
+        "Tables read mostly through large sequential scans"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Performance
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::TableStats]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        context
+            .seq_scan_tables
+            .iter()
+            .flatten()
+            .map(|table| {
+                RuleIssue::Performance(PerformanceIssue {
+                    issue_type: "High Sequential Scans".to_string(),
+                    severity: IssueSeverity::Warning,
+                    description: format!(
+                        "Table {}.{} has {} sequential scans",
+                        table.schemaname, table.relname, table.seq_scan
+                    ),
+                    recommendation: "Consider adding indexes to reduce sequential scans"
+                        .to_string(),
+                    details: Some(format!("Table: {}.{}", table.schemaname, table.relname)),
+                    object: Some(format!("{}.{}", table.schemaname, table.relname)),
+                })
+            })
+            .collect()
+    }
+}
+
+pub struct IndexHealthRule;
+
+impl Rule for IndexHealthRule {
+    fn id(&self) -> &str {
+        "index_health"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Indexes
+    }
+    fn description(&self) -> &str {
+        "Duplicate, redundant, invalid and unused indexes, and foreign keys without one"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Performance
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::Indexes]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        context
+            .index_report
+            .iter()
+            .flat_map(IndexAdvisor::issues)
+            .map(RuleIssue::Performance)
+            .collect()
+    }
+}
+
+pub struct BloatRule;
+
+impl Rule for BloatRule {
+    fn id(&self) -> &str {
+        "bloat"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Maintenance
+    }
+    fn description(&self) -> &str {
+        "Estimated table and btree index bloat"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Performance
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::Bloat]
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        let (Some(tables), Some(indexes)) = (&context.table_bloat, &context.index_bloat) else {
+            return Vec::new();
+        };
+        BloatCollector::issues(tables, indexes)
+            .into_iter()
+            .map(RuleIssue::Performance)
+            .collect()
+    }
+}
+
+pub struct WraparoundRule;
+
+impl Rule for WraparoundRule {
+    fn id(&self) -> &str {
+        "wraparound"
+    }
+    fn category(&self) -> RuleCategory {
+        RuleCategory::Maintenance
+    }
+    fn description(&self) -> &str {
+        "Transaction ID and multixact age approaching the freeze limits"
+    }
+    fn kind(&self) -> IssueKind {
+        IssueKind::Performance
+    }
+    fn inputs(&self) -> &'static [RuleInput] {
+        &[RuleInput::Wraparound]
+    }
+    /// mxid_age() was added in 9.5
+    fn min_version(&self) -> Option<i32> {
+        Some(90500)
+    }
+
+    fn evaluate(&self, context: &RuleContext) -> Vec<RuleIssue> {
+        context
+            .wraparound
+            .iter()
+            .flat_map(WraparoundAnalyzer::issues)
+            .map(RuleIssue::Performance)
+            .collect()
+    }
+}
+
+fn get_config_path() -> Option<PathBuf> {
+    storage::data_dir().map(|p| p.join("rules.json"))
+}
+
+fn load_config() -> RulesConfig {
+    let Some(path) = get_config_path() else {
+        return RulesConfig::default();
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Failed to parse rules config: {}", e);
+            RulesConfig::default()
+        }),
+        Err(_) => RulesConfig::default(),
+    }
+}
+
+fn save_config(config: &RulesConfig) -> Result<(), String> {
+    let Some(path) = get_config_path() else {
+        return Err("Could not determine config directory".to_string());
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize rules config: {}", e))?;
+
+    fs::write(&path, content).map_err(|e| format!("Failed to write rules config: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNDERSIZED: &str = include_str!("../../tests/fixtures/rules/pg16_undersized.json");
+    const AT_THRESHOLDS: &str = include_str!("../../tests/fixtures/rules/pg94_at_thresholds.json");
+
+    fn context(fixture: &str) -> RuleContext {
+        serde_json::from_str(fixture).expect("fixture is a valid RuleContext")
+    }
+
+    fn run(registry: &RuleRegistry, context: &RuleContext, kind: IssueKind) -> Vec<String> {
+        registry
+            .run(context, kind)
+            .issues
+            .into_iter()
+            .map(|(_, issue)| match issue {
+                RuleIssue::Config(i) => format!(
+                    "{:?} {}: {} -> {}",
+                    i.severity, i.parameter, i.current_value, i.recommended_value
+                ),
+                RuleIssue::Performance(i) => {
+                    format!("{:?} {}: {}", i.severity, i.issue_type, i.description)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undersized_server_config_issues() {
+        let registry = RuleRegistry::with_rules(builtin_rules());
+        // work_mem is exactly 4MB, which is not below the limit
+        assert_eq!(
+            run(&registry, &context(UNDERSIZED), IssueKind::Config),
+            vec![
+                "Warning shared_buffers: 128MB -> 4GB",
+                "Info effective_cache_size: 4GB -> 12GB",
+                "Warning max_connections: 500 -> 200",
+            ]
+        );
+    }
+
+    #[test]
+    fn undersized_server_performance_issues() {
+        let registry = RuleRegistry::with_rules(builtin_rules());
+        assert_eq!(
+            run(&registry, &context(UNDERSIZED), IssueKind::Performance),
+            vec![
+                "Critical Low Cache Hit Ratio: Cache hit ratio is 89.50%, should be > 90%",
+                "Warning High Sequential Scans: Table public.orders has 4210 sequential scans",
+                "Info Unused Index: Index public.orders_status_idx on orders (8 MB): Never scanned since statistics were reset",
+                "Warning Foreign Key Without Index: Foreign key order_items_order_id_fkey on public.order_items: Deletes and updates on orders scan order_items",
+                "Warning Table Bloat: Table public.events wastes 200 MB (50.0% of 400 MB)",
+                "Critical Index Bloat: Index public.events_pkey wastes 210 MB (84.0% of 250 MB)",
+                "Warning Transaction ID Wraparound: Database app has XID age 200000000 (9.3% of wraparound, autovacuum_freeze_max_age 200000000)",
+            ]
+        );
+    }
+
+    #[test]
+    fn values_at_thresholds_are_not_reported() {
+        let registry = RuleRegistry::with_rules(builtin_rules());
+        let context = context(AT_THRESHOLDS);
+        assert!(run(&registry, &context, IssueKind::Config).is_empty());
+        // The wraparound data would be critical, but the rule needs 9.5
+        assert!(run(&registry, &context, IssueKind::Performance).is_empty());
+    }
+
+    #[test]
+    fn wraparound_rule_applies_from_9_5() {
+        let registry = RuleRegistry::with_rules(builtin_rules());
+        let mut context = context(AT_THRESHOLDS);
+        context.version_num = 90500;
+        assert_eq!(
+            run(&registry, &context, IssueKind::Performance),
+            vec![
+                "Critical Transaction ID Wraparound: Database legacy has XID age 1700000000 (79.3% of wraparound, autovacuum_freeze_max_age 200000000)",
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_rules_are_skipped() {
+        let registry = RuleRegistry::with_rules(builtin_rules());
+        registry.config.lock().unwrap().disabled = vec![
+            "shared_buffers".to_string(),
+            "max_connections".to_string(),
+            "cache_hit_ratio".to_string(),
+            "index_health".to_string(),
+            "bloat".to_string(),
+        ];
+        let context = context(UNDERSIZED);
+
+        assert_eq!(
+            run(&registry, &context, IssueKind::Config),
+            vec!["Info effective_cache_size: 4GB -> 12GB"]
+        );
+        assert_eq!(
+            run(&registry, &context, IssueKind::Performance),
+            vec![
+                "Warning High Sequential Scans: Table public.orders has 4210 sequential scans",
+                "Warning Transaction ID Wraparound: Database app has XID age 200000000 (9.3% of wraparound, autovacuum_freeze_max_age 200000000)",
+            ]
+        );
+        assert!(!registry
+            .required_inputs(160004, IssueKind::Performance)
+            .contains(&RuleInput::Bloat));
+    }
+
+    #[test]
+    fn thresholds_from_context_are_used() {
+        let registry = RuleRegistry::with_rules(builtin_rules());
+        let mut context = context(UNDERSIZED);
+        context.thresholds.cache_hit_ratio_min = 85.0;
+        context.thresholds.max_connections_per_core = 100;
+        context.thresholds.max_connections_cap = 300;
+        context.thresholds.work_mem_min_mb = 8;
+
+        assert_eq!(
+            run(&registry, &context, IssueKind::Config),
+            vec![
+                "Warning shared_buffers: 128MB -> 4GB",
+                "Info effective_cache_size: 4GB -> 12GB",
+                "Info work_mem: 4MB -> 10-50MB",
+            ]
+        );
+        assert!(!run(&registry, &context, IssueKind::Performance)
+            .iter()
+            .any(|issue| issue.contains("Cache Hit Ratio")));
+    }
+
+    #[test]
+    fn excluded_rules_need_no_inputs() {
+        let registry = RuleRegistry::with_rules(builtin_rules()).without(&["bloat", "wraparound"]);
+        let inputs = registry.required_inputs(160000, IssueKind::Performance);
+
+        assert!(!inputs.contains(&RuleInput::Bloat));
+        assert!(!inputs.contains(&RuleInput::Wraparound));
+        assert!(inputs.contains(&RuleInput::CacheStats));
+        assert!(
+            !run(&registry, &context(UNDERSIZED), IssueKind::Performance)
+                .iter()
+                .any(|issue| issue.contains("Wraparound") || issue.contains("Bloat"))
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use super::connection::ConnectionManager;
+use super::history::{unix_now, HistoryMetric, MetricsStore, RetentionPolicy, SamplePoint};
+use super::queries::MetricsCollector;
+use super::storage;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Fields that are point-in-time values rather than cumulative counters
+const GAUGE_FIELDS: &[&str] = &["numbackends", "n_live_tup", "n_dead_tup", "size_bytes"];
+
+/// How often expired samples are rolled up
+const COMPACT_INTERVAL_SECS: i64 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplerConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Number of tables (by seq_tup_read) sampled per server
+    pub table_limit: i32,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        SamplerConfig {
+            enabled: true,
+            interval_secs: 15,
+            table_limit: 50,
+            retention: RetentionPolicy::default(),
+        }
+    }
+}
+
+/// Background task polling every connected server and persisting snapshots
+/// of `DatabaseStats`, `BgWriterStats`, `TableStats` and `DatabaseSize`
+#[derive(Clone)]
+pub struct MetricsSampler {
+    store: Arc<MetricsStore>,
+    config: Arc<Mutex<SamplerConfig>>,
+}
+
+impl MetricsSampler {
+    pub fn new() -> Self {
+        let store = storage::data_dir()
+            .ok_or_else(|| "Could not determine data directory".to_string())
+            .and_then(|dir| MetricsStore::open(&dir.join("metrics.db")))
+            .or_else(|e| {
+                eprintln!("{}; metrics history will not be persisted", e);
+                MetricsStore::open_in_memory()
+            })
+            .expect("failed to open metrics store");
+
+        MetricsSampler {
+            store: Arc::new(store),
+            config: Arc::new(Mutex::new(load_config())),
+        }
+    }
+
+    pub fn store(&self) -> &MetricsStore {
+        &self.store
+    }
+
+    pub fn config(&self) -> SamplerConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: SamplerConfig) -> Result<(), String> {
+        if config.interval_secs == 0 {
+            return Err("Sampling interval must be at least 1 second".to_string());
+        }
+        if config.retention.raw_hours <= 0 || config.retention.minute_days <= 0 || config.retention.hour_days <= 0 {
+            return Err("Retention periods must be positive".to_string());
+        }
+
+        save_config(&config)?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// Sampling loop; runs for the lifetime of the app
+    pub async fn run(self, manager: ConnectionManager) {
+        let mut last_compact = 0;
+
+        loop {
+            let config = self.config();
+
+            if config.enabled {
+                for server_id in manager.connected_server_ids() {
+                    self.sample_server(&manager, &server_id, &config).await;
+                }
+            }
+
+            let now = unix_now();
+            if now - last_compact >= COMPACT_INTERVAL_SECS {
+                if let Err(e) = self.store.compact(&config.retention, now) {
+                    eprintln!("Metrics compaction failed: {}", e);
+                }
+                last_compact = now;
+            }
+
+            tokio::time::sleep(Duration::from_secs(config.interval_secs)).await;
+        }
+    }
+
+    /// Sample each metric family on its own, so one failing collector does
+    /// not drop the others for this tick
+    async fn sample_server(
+        &self,
+        manager: &ConnectionManager,
+        server_id: &str,
+        config: &SamplerConfig,
+    ) {
+        let ts = unix_now();
+
+        let databases = MetricsCollector::get_database_stats(manager, server_id)
+            .await
+            .map(|databases| {
+                databases
+                    .iter()
+                    .flat_map(|db| to_points(db, &db.datname))
+                    .collect()
+            });
+        self.record(server_id, HistoryMetric::DatabaseStats, ts, databases);
+
+        let bgwriter = MetricsCollector::get_bgwriter_stats(manager, server_id)
+            .await
+            .map(|bgwriter| to_points(&bgwriter, ""));
+        self.record(server_id, HistoryMetric::BgWriterStats, ts, bgwriter);
+
+        let tables = MetricsCollector::get_table_stats(manager, server_id, config.table_limit)
+            .await
+            .map(|tables| {
+                tables
+                    .iter()
+                    .flat_map(|t| to_points(t, &format!("{}.{}", t.schemaname, t.relname)))
+                    .collect()
+            });
+        self.record(server_id, HistoryMetric::TableStats, ts, tables);
+
+        let sizes = MetricsCollector::get_database_sizes(manager, server_id)
+            .await
+            .map(|sizes| {
+                sizes
+                    .iter()
+                    .flat_map(|s| to_points(s, &s.database_name))
+                    .collect()
+            });
+        self.record(server_id, HistoryMetric::DatabaseSize, ts, sizes);
+    }
+
+    /// Store one metric family, logging a failed collection or write
+    fn record(
+        &self,
+        server_id: &str,
+        metric: HistoryMetric,
+        ts: i64,
+        points: Result<Vec<SamplePoint>, String>,
+    ) {
+        let result = points.and_then(|points| self.store.record(server_id, metric, ts, &points));
+        if let Err(e) = result {
+            eprintln!(
+                "Metrics sampling of {} failed for {}: {}",
+                metric.as_str(),
+                server_id,
+                e
+            );
+        }
+    }
+}
+
+impl Default for MetricsSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Flatten the numeric fields of a snapshot struct into sample points
+fn to_points<T: Serialize>(item: &T, object: &str) -> Vec<SamplePoint> {
+    let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(item) else {
+        return Vec::new();
+    };
+
+    fields
+        .into_iter()
+        .filter_map(|(field, value)| {
+            let value = value.as_f64()?;
+            Some(SamplePoint {
+                object: object.to_string(),
+                counter: !GAUGE_FIELDS.contains(&field.as_str()),
+                field,
+                value,
+            })
+        })
+        .collect()
+}
+
+fn get_config_path() -> Option<PathBuf> {
+    storage::data_dir().map(|p| p.join("sampler.json"))
+}
+
+fn load_config() -> SamplerConfig {
+    let Some(path) = get_config_path() else {
+        return SamplerConfig::default();
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Failed to parse sampler config: {}", e);
+            SamplerConfig::default()
+        }),
+        Err(_) => SamplerConfig::default(),
+    }
+}
+
+fn save_config(config: &SamplerConfig) -> Result<(), String> {
+    let Some(path) = get_config_path() else {
+        return Err("Could not determine config directory".to_string());
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize sampler config: {}", e))?;
+
+    fs::write(&path, content).map_err(|e| format!("Failed to write sampler config: {}", e))
+}
@@ -0,0 +1,31 @@
+use super::issue_history::IssueHistory;
+use super::progress::ProgressTracker;
+use super::rates::RateEngine;
+use super::sampler::MetricsSampler;
+use super::statements::StatementSnapshots;
+use super::wraparound::XidRateTracker;
+
+/// Handles to everything kept per server outside the connection pools, so a
+/// removed server is forgotten in one place. Each handle shares its state
+/// with the instance managed on its own.
+#[derive(Clone)]
+pub struct ServerData {
+    pub sampler: MetricsSampler,
+    pub rates: RateEngine,
+    pub snapshots: StatementSnapshots,
+    pub progress: ProgressTracker,
+    pub xid_tracker: XidRateTracker,
+    pub issue_history: IssueHistory,
+}
+
+impl ServerData {
+    /// Drop samples, counters, snapshots and issue history of a server
+    pub fn forget_server(&self, server_id: &str) -> Result<(), String> {
+        self.rates.forget(server_id);
+        self.snapshots.forget(server_id);
+        self.progress.forget(server_id);
+        self.xid_tracker.forget(server_id);
+        self.issue_history.forget(server_id)?;
+        self.sampler.store().delete_server(server_id)
+    }
+}
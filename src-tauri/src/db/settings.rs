@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingType {
+    Bool,
+    Integer,
+    Real,
+    String,
+    Enum,
+}
+
+/// What a numeric setting measures once its unit is applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingQuantity {
+    Bytes,
+    Milliseconds,
+    /// Counts, ratios and other unitless numbers
+    Plain,
+}
+
+/// One pg_settings row with its value interpreted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypedSetting {
+    pub name: String,
+    /// pg_settings.setting, as stored
+    pub raw: String,
+    pub unit: Option<String>,
+    pub setting_type: SettingType,
+    pub quantity: Option<SettingQuantity>,
+    /// In bytes or milliseconds for memory and time settings. Negative
+    /// values (usually -1 for "disabled" or "use default") are kept as is.
+    pub value: Option<f64>,
+    pub bool_value: Option<bool>,
+    /// Allowed values of enum settings
+    pub enum_values: Option<Vec<String>>,
+    /// Value with the largest exact unit, e.g. "128MB" or "5min"
+    pub display: String,
+    pub category: String,
+    pub source: String,
+    pub context: String,
+}
+
+impl TypedSetting {
+    /// Interpret a row of name, setting, unit, vartype, enumvals, category,
+    /// source and context from pg_settings
+    pub fn from_row(row: &tokio_postgres::Row) -> Self {
+        let name: String = row.get(0);
+        let raw: String = row.get(1);
+        let unit: Option<String> = row.get(2);
+        let vartype: &str = row.get(3);
+        let enum_values: Option<Vec<String>> = row.get(4);
+
+        let setting_type = match vartype {
+            "bool" => SettingType::Bool,
+            "integer" => SettingType::Integer,
+            "real" => SettingType::Real,
+            "enum" => SettingType::Enum,
+            _ => SettingType::String,
+        };
+
+        let (quantity, value) = match setting_type {
+            SettingType::Integer | SettingType::Real => {
+                let (quantity, scale) = parse_unit(unit.as_deref());
+                (Some(quantity), raw.parse::<f64>().ok().map(|v| v * scale))
+            }
+            _ => (None, None),
+        };
+
+        let bool_value = match setting_type {
+            SettingType::Bool => Some(raw == "on"),
+            _ => None,
+        };
+
+        let display = match (quantity, value) {
+            (Some(SettingQuantity::Bytes), Some(bytes)) if bytes >= 0.0 => format_bytes(bytes),
+            (Some(SettingQuantity::Milliseconds), Some(ms)) if ms >= 0.0 => format_millis(ms),
+            _ => raw.clone(),
+        };
+
+        TypedSetting {
+            name,
+            raw,
+            unit,
+            setting_type,
+            quantity,
+            value,
+            bool_value,
+            enum_values,
+            display,
+            category: row.get(5),
+            source: row.get(6),
+            context: row.get(7),
+        }
+    }
+}
+
+/// All settings of a server with typed accessors for analyzer rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerSettings {
+    pub settings: Vec<TypedSetting>,
+}
+
+impl ServerSettings {
+    pub fn new(settings: Vec<TypedSetting>) -> Self {
+        ServerSettings { settings }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TypedSetting> {
+        self.settings.iter().find(|s| s.name == name)
+    }
+
+    /// Memory setting in bytes; None for other kinds or negative values
+    pub fn bytes(&self, name: &str) -> Option<i64> {
+        self.get(name)
+            .filter(|s| s.quantity == Some(SettingQuantity::Bytes))
+            .and_then(|s| s.value)
+            .filter(|v| *v >= 0.0)
+            .map(|v| v as i64)
+    }
+
+    /// Unitless integer or real setting
+    pub fn number(&self, name: &str) -> Option<f64> {
+        self.get(name)
+            .filter(|s| s.quantity == Some(SettingQuantity::Plain))
+            .and_then(|s| s.value)
+    }
+}
+
+/// Quantity and multiplier of a pg_settings unit. Block-based settings
+/// carry their page size in the unit ("8kB", or "16kB" on servers built
+/// with larger pages), so no separate block_size lookup is needed.
+fn parse_unit(unit: Option<&str>) -> (SettingQuantity, f64) {
+    let Some(unit) = unit else {
+        return (SettingQuantity::Plain, 1.0);
+    };
+
+    let split = unit
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(unit.len());
+    let count = unit[..split].parse::<f64>().unwrap_or(1.0);
+
+    match &unit[split..] {
+        "B" => (SettingQuantity::Bytes, count),
+        "kB" => (SettingQuantity::Bytes, count * 1024.0),
+        "MB" => (SettingQuantity::Bytes, count * 1024.0 * 1024.0),
+        "GB" => (SettingQuantity::Bytes, count * 1024.0 * 1024.0 * 1024.0),
+        "TB" => (
+            SettingQuantity::Bytes,
+            count * 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        ),
+        "us" => (SettingQuantity::Milliseconds, count / 1000.0),
+        "ms" => (SettingQuantity::Milliseconds, count),
+        "s" => (SettingQuantity::Milliseconds, count * 1000.0),
+        "min" => (SettingQuantity::Milliseconds, count * 60_000.0),
+        "h" => (SettingQuantity::Milliseconds, count * 3_600_000.0),
+        "d" => (SettingQuantity::Milliseconds, count * 86_400_000.0),
+        _ => (SettingQuantity::Plain, 1.0),
+    }
+}
+
+/// Bytes in the largest unit that divides them exactly, as PostgreSQL
+/// prints memory settings
+pub fn format_bytes(bytes: f64) -> String {
+    let bytes = bytes as i64;
+    let units = [
+        ("TB", 1i64 << 40),
+        ("GB", 1 << 30),
+        ("MB", 1 << 20),
+        ("kB", 1 << 10),
+    ];
+    units
+        .iter()
+        .find(|(_, size)| bytes >= *size && bytes % size == 0)
+        .map(|(unit, size)| format!("{}{}", bytes / size, unit))
+        .unwrap_or_else(|| format!("{}B", bytes))
+}
+
+/// Milliseconds in the largest unit that divides them exactly
+pub fn format_millis(ms: f64) -> String {
+    if ms.fract() != 0.0 {
+        return format!("{}ms", ms);
+    }
+    let ms = ms as i64;
+    let units = [
+        ("d", 86_400_000i64),
+        ("h", 3_600_000),
+        ("min", 60_000),
+        ("s", 1000),
+    ];
+    units
+        .iter()
+        .find(|(_, size)| ms >= *size && ms % size == 0)
+        .map(|(unit, size)| format!("{}{}", ms / size, unit))
+        .unwrap_or_else(|| format!("{}ms", ms))
+}
@@ -0,0 +1,246 @@
+use super::connection::ConnectionManager;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationSizeSort {
+    #[default]
+    Total,
+    Heap,
+    Toast,
+    Indexes,
+    Name,
+}
+
+impl RelationSizeSort {
+    fn column(&self) -> &'static str {
+        match self {
+            RelationSizeSort::Total => "total_bytes",
+            RelationSizeSort::Heap => "heap_bytes",
+            RelationSizeSort::Toast => "toast_bytes",
+            RelationSizeSort::Indexes => "index_bytes",
+            RelationSizeSort::Name => "schemaname, relname",
+        }
+    }
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Page of the relation size inventory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationSizeRequest {
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+    #[serde(default)]
+    pub sort_by: RelationSizeSort,
+    #[serde(default = "default_true")]
+    pub descending: bool,
+    /// Only relations in this schema
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Report partitions as part of their top-level partitioned table
+    #[serde(default = "default_true")]
+    pub rollup_partitions: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationSize {
+    pub schemaname: String,
+    pub relname: String,
+    /// pg_class.relkind: r, p (partitioned) or m
+    pub relkind: String,
+    /// Tablespace of the table (of its first partition when rolled up)
+    pub tablespace: Option<String>,
+    /// Main fork plus free space and visibility maps
+    pub heap_bytes: i64,
+    /// TOAST table and its index
+    pub toast_bytes: i64,
+    pub index_bytes: i64,
+    pub total_bytes: i64,
+    pub row_estimate: i64,
+    /// Leaf partitions rolled up into this row
+    pub partition_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaSize {
+    pub schemaname: String,
+    pub relation_count: i64,
+    pub heap_bytes: i64,
+    pub toast_bytes: i64,
+    pub index_bytes: i64,
+    pub total_bytes: i64,
+}
+
+/// Totals by the tablespace of each table or partition; indexes are counted
+/// with their table even if stored elsewhere
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TablespaceSize {
+    pub tablespace: String,
+    pub relation_count: i64,
+    pub total_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationSizeInventory {
+    pub relations: Vec<RelationSize>,
+    /// Rows matching the request before paging
+    pub total_count: i64,
+    pub schemas: Vec<SchemaSize>,
+    pub tablespaces: Vec<TablespaceSize>,
+}
+
+/// Per-table sizes of the connected database (`sizes`), each keyed by the
+/// row it is reported under: itself, or its partition root when $1 is set.
+/// `rolled` sums them per reported row. $2 optionally filters the schema.
+const SIZES_CTE: &str = r#"
+    WITH RECURSIVE tree AS (
+        SELECT c.oid AS relid, c.oid AS root
+        FROM pg_class c
+        WHERE c.relkind IN ('r', 'p', 'm') AND NOT c.relispartition
+        UNION ALL
+        SELECT i.inhrelid, CASE WHEN $1 THEN t.root ELSE i.inhrelid END
+        FROM pg_inherits i
+        JOIN tree t ON i.inhparent = t.relid
+        JOIN pg_class c ON c.oid = i.inhrelid
+        WHERE c.relispartition
+    ),
+    sizes AS (
+        SELECT
+            t.root,
+            t.relid <> t.root AS is_partition,
+            c.relkind,
+            c.reltuples,
+            COALESCE(ts.spcname, (
+                SELECT dts.spcname FROM pg_database d
+                JOIN pg_tablespace dts ON dts.oid = d.dattablespace
+                WHERE d.datname = current_database()
+            ))::text AS tablespace,
+            pg_table_size(c.oid) AS table_bytes,
+            CASE WHEN c.reltoastrelid <> 0
+                THEN pg_total_relation_size(c.reltoastrelid) ELSE 0 END AS toast_bytes,
+            pg_indexes_size(c.oid) AS index_bytes,
+            pg_total_relation_size(c.oid) AS total_bytes
+        FROM tree t
+        JOIN pg_class c ON c.oid = t.relid
+        JOIN pg_class r ON r.oid = t.root
+        JOIN pg_namespace n ON n.oid = r.relnamespace
+        LEFT JOIN pg_tablespace ts ON ts.oid = c.reltablespace
+        WHERE c.relkind IN ('r', 'p', 'm')
+            AND ($1 OR r.relkind <> 'p')
+            AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND n.nspname !~ '^pg_toast'
+            AND ($2::text IS NULL OR n.nspname = $2)
+    ),
+    rolled AS (
+        SELECT
+            n.nspname::text AS schemaname,
+            r.relname::text AS relname,
+            r.relkind::text AS relkind,
+            min(s.tablespace) FILTER (WHERE s.relkind <> 'p') AS tablespace,
+            sum(s.table_bytes - s.toast_bytes)::bigint AS heap_bytes,
+            sum(s.toast_bytes)::bigint AS toast_bytes,
+            sum(s.index_bytes)::bigint AS index_bytes,
+            sum(s.total_bytes)::bigint AS total_bytes,
+            sum(GREATEST(s.reltuples, 0))::bigint AS row_estimate,
+            count(*) FILTER (WHERE s.is_partition AND s.relkind <> 'p') AS partition_count
+        FROM sizes s
+        JOIN pg_class r ON r.oid = s.root
+        JOIN pg_namespace n ON n.oid = r.relnamespace
+        GROUP BY n.nspname, r.relname, r.relkind
+    )
+"#;
+
+pub struct SizeCollector;
+
+impl SizeCollector {
+    pub async fn get_relation_sizes(
+        manager: &ConnectionManager,
+        server_id: &str,
+        request: &RelationSizeRequest,
+    ) -> Result<RelationSizeInventory, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let direction = if request.descending { "DESC" } else { "ASC" };
+        let order = match request.sort_by {
+            RelationSizeSort::Name => format!("schemaname {0}, relname {0}", direction),
+            sort => format!("{} {}, schemaname, relname", sort.column(), direction),
+        };
+
+        // One statement, so the sizes are computed once for the page and
+        // the totals; totals ignore paging and partitions always count once
+        let query = format!(
+            r#"
+            {SIZES_CTE},
+            page AS (
+                SELECT *, row_number() OVER (ORDER BY {order}) AS n
+                FROM rolled
+                ORDER BY {order}
+                LIMIT $3 OFFSET $4
+            ),
+            schemas AS (
+                SELECT
+                    schemaname,
+                    count(*) AS relation_count,
+                    sum(heap_bytes)::bigint AS heap_bytes,
+                    sum(toast_bytes)::bigint AS toast_bytes,
+                    sum(index_bytes)::bigint AS index_bytes,
+                    sum(total_bytes)::bigint AS total_bytes
+                FROM rolled
+                GROUP BY schemaname
+            ),
+            -- Per physical table, so partitions count where they are stored
+            tablespaces AS (
+                SELECT
+                    tablespace,
+                    count(*) AS relation_count,
+                    sum(total_bytes)::bigint AS total_bytes
+                FROM sizes
+                WHERE relkind <> 'p'
+                GROUP BY tablespace
+            )
+            SELECT
+                (SELECT count(*) FROM rolled),
+                COALESCE((SELECT json_agg(p ORDER BY n) FROM page p), '[]'),
+                COALESCE((SELECT json_agg(s ORDER BY total_bytes DESC) FROM schemas s), '[]'),
+                COALESCE((SELECT json_agg(t ORDER BY total_bytes DESC) FROM tablespaces t), '[]')
+            "#
+        );
+
+        let row = client
+            .query_one(
+                &query,
+                &[
+                    &request.rollup_partitions,
+                    &request.schema,
+                    &request.limit.max(0),
+                    &request.offset.max(0),
+                ],
+            )
+            .await
+            .map_err(|e| format!("Failed to query relation sizes: {}", e))?;
+
+        let total_count: i64 = row.get(0);
+        let relations: Vec<RelationSize> = serde_json::from_value(row.get(1))
+            .map_err(|e| format!("Failed to read relation sizes: {}", e))?;
+        let schemas: Vec<SchemaSize> = serde_json::from_value(row.get(2))
+            .map_err(|e| format!("Failed to read schema sizes: {}", e))?;
+        let tablespaces: Vec<TablespaceSize> = serde_json::from_value(row.get(3))
+            .map_err(|e| format!("Failed to read tablespace sizes: {}", e))?;
+
+        Ok(RelationSizeInventory {
+            relations,
+            total_count,
+            schemas,
+            tablespaces,
+        })
+    }
+}
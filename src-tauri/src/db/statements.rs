@@ -0,0 +1,371 @@
+use super::capabilities::ExtensionVersion;
+use super::connection::ConnectionManager;
+use super::history::unix_now;
+use super::metrics::{QuerySortKey, QueryStat};
+use super::queries::MetricsCollector;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Snapshots kept per server; the oldest are dropped first
+const MAX_SNAPSHOTS_PER_SERVER: usize = 60;
+
+/// Identity of a pg_stat_statements entry
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatementKey {
+    pub queryid: i64,
+    pub userid: u32,
+    pub dbid: u32,
+}
+
+/// Cumulative counters of one statement. Top-level and nested entries
+/// sharing a key are summed.
+#[derive(Debug, Clone, Default)]
+struct StatementCounters {
+    query: String,
+    calls: i64,
+    total_exec_time: f64,
+    total_plan_time: Option<f64>,
+    rows: i64,
+    shared_blks_hit: i64,
+    shared_blks_read: i64,
+    local_blks_read: i64,
+    temp_blks_read: i64,
+    temp_blks_written: i64,
+    wal_bytes: Option<i64>,
+}
+
+impl StatementCounters {
+    fn add(&mut self, stat: &QueryStat) {
+        if self.query.is_empty() {
+            self.query = stat.query.clone();
+        }
+        self.calls += stat.calls;
+        self.total_exec_time += stat.total_exec_time;
+        self.total_plan_time = add_opt(self.total_plan_time, stat.total_plan_time);
+        self.rows += stat.rows;
+        self.shared_blks_hit += stat.shared_blks_hit;
+        self.shared_blks_read += stat.shared_blks_read;
+        self.local_blks_read += stat.local_blks_read;
+        self.temp_blks_read += stat.temp_blks_read;
+        self.temp_blks_written += stat.temp_blks_written;
+        self.wal_bytes = add_opt(self.wal_bytes, stat.wal_bytes);
+    }
+}
+
+fn add_opt<T: std::ops::Add<Output = T>>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+struct StatementSnapshot {
+    id: String,
+    taken_at: i64,
+    /// pg_stat_statements_info (1.9+)
+    stats_reset: Option<String>,
+    dealloc: Option<i64>,
+    entries: HashMap<StatementKey, StatementCounters>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    /// Unix timestamp in seconds
+    pub taken_at: i64,
+    pub entries: usize,
+}
+
+impl From<&StatementSnapshot> for SnapshotInfo {
+    fn from(snapshot: &StatementSnapshot) -> Self {
+        SnapshotInfo {
+            id: snapshot.id.clone(),
+            taken_at: snapshot.taken_at,
+            entries: snapshot.entries.len(),
+        }
+    }
+}
+
+/// Activity of one statement between two snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryDelta {
+    pub queryid: i64,
+    pub userid: u32,
+    pub dbid: u32,
+    pub query: String,
+    pub calls: i64,
+    pub total_exec_time: f64,
+    pub mean_exec_time: f64,
+    pub total_plan_time: Option<f64>,
+    pub rows: i64,
+    pub shared_blks_hit: i64,
+    pub shared_blks_read: i64,
+    pub local_blks_read: i64,
+    pub temp_blks_read: i64,
+    pub temp_blks_written: i64,
+    pub wal_bytes: Option<i64>,
+    /// Not present in the first snapshot
+    pub new_entry: bool,
+    /// Counters went backwards, so the entry was evicted and re-added (or
+    /// reset); the delta counts from zero and is a lower bound
+    pub reset: bool,
+}
+
+/// Entry present in the first snapshot but gone from the second
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvictedStatement {
+    pub queryid: i64,
+    pub userid: u32,
+    pub dbid: u32,
+    pub query: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementsDiff {
+    pub from: SnapshotInfo,
+    pub to: SnapshotInfo,
+    pub interval_secs: i64,
+    /// pg_stat_statements_reset() was called in between; all deltas count
+    /// from zero
+    pub stats_reset: bool,
+    /// Entries deallocated by the extension in between (1.9+)
+    pub deallocations: Option<i64>,
+    pub queries: Vec<QueryDelta>,
+    pub evicted: Vec<EvictedStatement>,
+}
+
+/// In-memory pg_stat_statements snapshots per server, for computing what
+/// ran during a window rather than since the last reset
+#[derive(Clone, Default)]
+pub struct StatementSnapshots {
+    snapshots: Arc<Mutex<HashMap<String, Vec<StatementSnapshot>>>>,
+}
+
+impl StatementSnapshots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn take_snapshot(
+        &self,
+        manager: &ConnectionManager,
+        server_id: &str,
+    ) -> Result<SnapshotInfo, String> {
+        let client = manager.get_client(server_id).await?;
+
+        let version = MetricsCollector::pg_stat_statements_version(&client)
+            .await?
+            .ok_or_else(|| "pg_stat_statements extension is not installed".to_string())?;
+
+        let query = MetricsCollector::statements_query(version, None)?;
+        let rows = client
+            .query(&query, &[])
+            .await
+            .map_err(|e| format!("Failed to query pg_stat_statements: {}", e))?;
+
+        let mut entries: HashMap<StatementKey, StatementCounters> = HashMap::new();
+        for row in &rows {
+            let stat = MetricsCollector::query_stat_from_row(row);
+            // Entries without a query id cannot be matched across snapshots
+            let Some(queryid) = stat.queryid else {
+                continue;
+            };
+            let key = StatementKey {
+                queryid,
+                userid: stat.userid,
+                dbid: stat.dbid,
+            };
+            entries.entry(key).or_default().add(&stat);
+        }
+
+        let (stats_reset, dealloc) = Self::query_info(&client, version).await?;
+
+        let snapshot = StatementSnapshot {
+            id: uuid::Uuid::new_v4().to_string(),
+            taken_at: unix_now(),
+            stats_reset,
+            dealloc,
+            entries,
+        };
+        let info = SnapshotInfo::from(&snapshot);
+
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let list = snapshots.entry(server_id.to_string()).or_default();
+        list.push(snapshot);
+        if list.len() > MAX_SNAPSHOTS_PER_SERVER {
+            list.remove(0);
+        }
+
+        Ok(info)
+    }
+
+    async fn query_info(
+        client: &tokio_postgres::Client,
+        version: ExtensionVersion,
+    ) -> Result<(Option<String>, Option<i64>), String> {
+        if !version.at_least(1, 9) {
+            return Ok((None, None));
+        }
+
+        let row = client
+            .query_one("SELECT stats_reset::text, dealloc FROM pg_stat_statements_info", &[])
+            .await
+            .map_err(|e| format!("Failed to query pg_stat_statements_info: {}", e))?;
+
+        Ok((row.get(0), row.get(1)))
+    }
+
+    pub fn list_snapshots(&self, server_id: &str) -> Vec<SnapshotInfo> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .get(server_id)
+            .map(|list| list.iter().map(SnapshotInfo::from).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn forget(&self, server_id: &str) {
+        self.snapshots.lock().unwrap().remove(server_id);
+    }
+
+    /// Per-statement deltas from one snapshot to a later one
+    pub fn diff(
+        &self,
+        server_id: &str,
+        from_id: &str,
+        to_id: &str,
+        sort: QuerySortKey,
+        limit: usize,
+    ) -> Result<StatementsDiff, String> {
+        let snapshots = self.snapshots.lock().unwrap();
+        let list = snapshots
+            .get(server_id)
+            .ok_or_else(|| format!("No snapshots for server: {}", server_id))?;
+
+        let find = |id: &str| {
+            list.iter()
+                .find(|s| s.id == id)
+                .ok_or_else(|| format!("Snapshot not found: {}", id))
+        };
+        let mut from = find(from_id)?;
+        let mut to = find(to_id)?;
+        if from.taken_at > to.taken_at {
+            std::mem::swap(&mut from, &mut to);
+        }
+
+        Ok(compare(from, to, sort, limit))
+    }
+}
+
+fn compare(
+    from: &StatementSnapshot,
+    to: &StatementSnapshot,
+    sort: QuerySortKey,
+    limit: usize,
+) -> StatementsDiff {
+    let stats_reset = from.stats_reset != to.stats_reset;
+    let deallocations = match (from.dealloc, to.dealloc) {
+        (Some(a), Some(b)) if !stats_reset && b >= a => Some(b - a),
+        (_, Some(b)) if stats_reset => Some(b),
+        _ => None,
+    };
+
+    let mut queries: Vec<QueryDelta> = to
+        .entries
+        .iter()
+        .filter_map(|(key, cur)| {
+            let prev = if stats_reset { None } else { from.entries.get(key) };
+            let delta = entry_delta(key, prev, cur, stats_reset);
+            (delta.calls > 0 || delta.reset).then_some(delta)
+        })
+        .collect();
+
+    queries.sort_by(|a, b| sort_value(b, sort).total_cmp(&sort_value(a, sort)));
+    queries.truncate(limit);
+
+    let evicted = if stats_reset {
+        Vec::new()
+    } else {
+        from.entries
+            .iter()
+            .filter(|(key, _)| !to.entries.contains_key(*key))
+            .map(|(key, prev)| EvictedStatement {
+                queryid: key.queryid,
+                userid: key.userid,
+                dbid: key.dbid,
+                query: prev.query.clone(),
+            })
+            .collect()
+    };
+
+    StatementsDiff {
+        from: SnapshotInfo::from(from),
+        to: SnapshotInfo::from(to),
+        interval_secs: to.taken_at - from.taken_at,
+        stats_reset,
+        deallocations,
+        queries,
+        evicted,
+    }
+}
+
+fn entry_delta(
+    key: &StatementKey,
+    prev: Option<&StatementCounters>,
+    cur: &StatementCounters,
+    stats_reset: bool,
+) -> QueryDelta {
+    // A statement whose counters shrank was evicted and re-added in between
+    let went_backwards = prev.is_some_and(|p| {
+        cur.calls < p.calls
+            || cur.total_exec_time < p.total_exec_time
+            || cur.rows < p.rows
+            || cur.shared_blks_hit < p.shared_blks_hit
+            || cur.shared_blks_read < p.shared_blks_read
+            || cur.local_blks_read < p.local_blks_read
+            || cur.temp_blks_read < p.temp_blks_read
+            || cur.total_plan_time.unwrap_or(0.0) < p.total_plan_time.unwrap_or(0.0)
+    });
+    let base = if went_backwards { None } else { prev };
+    let zero = StatementCounters::default();
+    let prev = base.unwrap_or(&zero);
+
+    let calls = cur.calls - prev.calls;
+    let total_exec_time = cur.total_exec_time - prev.total_exec_time;
+
+    QueryDelta {
+        queryid: key.queryid,
+        userid: key.userid,
+        dbid: key.dbid,
+        query: cur.query.clone(),
+        calls,
+        total_exec_time,
+        mean_exec_time: if calls > 0 { total_exec_time / calls as f64 } else { 0.0 },
+        total_plan_time: cur
+            .total_plan_time
+            .map(|t| t - prev.total_plan_time.unwrap_or(0.0)),
+        rows: cur.rows - prev.rows,
+        shared_blks_hit: cur.shared_blks_hit - prev.shared_blks_hit,
+        shared_blks_read: cur.shared_blks_read - prev.shared_blks_read,
+        local_blks_read: cur.local_blks_read - prev.local_blks_read,
+        temp_blks_read: cur.temp_blks_read - prev.temp_blks_read,
+        temp_blks_written: (cur.temp_blks_written - prev.temp_blks_written).max(0),
+        wal_bytes: cur.wal_bytes.map(|w| (w - prev.wal_bytes.unwrap_or(0)).max(0)),
+        new_entry: base.is_none() && !went_backwards && !stats_reset,
+        reset: went_backwards || stats_reset,
+    }
+}
+
+fn sort_value(delta: &QueryDelta, sort: QuerySortKey) -> f64 {
+    match sort {
+        QuerySortKey::TotalTime => delta.total_exec_time,
+        QuerySortKey::MeanTime => delta.mean_exec_time,
+        QuerySortKey::Calls => delta.calls as f64,
+        QuerySortKey::Io => {
+            (delta.shared_blks_read + delta.local_blks_read + delta.temp_blks_read) as f64
+        }
+        QuerySortKey::Temp => delta.temp_blks_written as f64,
+        QuerySortKey::Wal => delta.wal_bytes.unwrap_or(0) as f64,
+    }
+}
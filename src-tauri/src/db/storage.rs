@@ -1,5 +1,7 @@
+use crate::db::types::{ConnectionConfig, SshTunnelConfig, SslMode};
 use keyring::Entry;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
@@ -15,17 +17,64 @@ pub struct SavedServerConfig {
     pub database: String,
     pub username: String,
     pub use_ssl: bool,
+    #[serde(default)]
+    pub ssl_mode: Option<SslMode>,
+    #[serde(default)]
+    pub ssl_root_cert: Option<String>,
+    #[serde(default)]
+    pub ssl_client_cert: Option<String>,
+    #[serde(default)]
+    pub ssl_client_key: Option<String>,
+    #[serde(default)]
+    pub ssh_tunnel: Option<SshTunnelConfig>,
     #[serde(skip)]
     pub password: String, // Not serialized - stored in OS keyring
 }
 
+impl From<&ConnectionConfig> for SavedServerConfig {
+    fn from(config: &ConnectionConfig) -> Self {
+        SavedServerConfig {
+            id: config.id.clone(),
+            name: config.name.clone(),
+            host: config.host.clone(),
+            port: config.port,
+            database: config.database.clone(),
+            username: config.username.clone(),
+            use_ssl: config.use_ssl,
+            ssl_mode: config.ssl_mode,
+            ssl_root_cert: config.ssl_root_cert.clone(),
+            ssl_client_cert: config.ssl_client_cert.clone(),
+            ssl_client_key: config.ssl_client_key.clone(),
+            ssh_tunnel: config.ssh_tunnel.clone(),
+            password: config.password.clone(), // Store password for reconnect
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct StoredData {
     pub servers: Vec<SavedServerConfig>,
 }
 
+/// Host sizing entered by the user for a server whose hardware cannot be
+/// read through PostgreSQL; unset fields are still detected
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HardwareProfile {
+    #[serde(default)]
+    pub cpu_cores: Option<i32>,
+    #[serde(default)]
+    pub total_memory_mb: Option<i64>,
+    #[serde(default)]
+    pub os_type: Option<String>,
+}
+
+/// App data directory holding servers.json and other local state
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|p| p.join("pg-dashboard"))
+}
+
 fn get_config_path() -> Option<PathBuf> {
-    dirs::data_local_dir().map(|p| p.join("pg-dashboard").join("servers.json"))
+    data_dir().map(|p| p.join("servers.json"))
 }
 
 pub fn load_servers() -> Vec<SavedServerConfig> {
@@ -46,6 +95,10 @@ pub fn load_servers() -> Vec<SavedServerConfig> {
                         .into_iter()
                         .map(|mut server| {
                             server.password = get_password_from_keyring(&server.id).unwrap_or_default();
+                            if let Some(ssh) = server.ssh_tunnel.as_mut() {
+                                ssh.passphrase = get_password_from_keyring(&ssh_passphrase_account(&server.id))
+                                    .unwrap_or_default();
+                            }
                             server
                         })
                         .collect()
@@ -63,6 +116,11 @@ pub fn load_servers() -> Vec<SavedServerConfig> {
     }
 }
 
+/// Keyring account holding the SSH key passphrase of a server
+fn ssh_passphrase_account(server_id: &str) -> String {
+    format!("{}:ssh", server_id)
+}
+
 /// Get password from OS keyring
 fn get_password_from_keyring(server_id: &str) -> Option<String> {
     Entry::new(KEYRING_SERVICE, server_id)
@@ -117,6 +175,9 @@ pub fn add_server(config: &SavedServerConfig) -> Result<(), String> {
     if !config.password.is_empty() {
         set_password_in_keyring(&config.id, &config.password)?;
     }
+    if let Some(ssh) = config.ssh_tunnel.as_ref().filter(|ssh| !ssh.passphrase.is_empty()) {
+        set_password_in_keyring(&ssh_passphrase_account(&config.id), &ssh.passphrase)?;
+    }
 
     let mut servers = load_servers();
 
@@ -130,12 +191,67 @@ pub fn add_server(config: &SavedServerConfig) -> Result<(), String> {
 pub fn remove_server(id: &str) -> Result<(), String> {
     // Delete password from keyring
     delete_password_from_keyring(id)?;
+    delete_password_from_keyring(&ssh_passphrase_account(id))?;
+
+    let mut profiles = load_hardware_profiles();
+    if profiles.remove(id).is_some() {
+        save_hardware_profiles(&profiles)?;
+    }
 
     let mut servers = load_servers();
     servers.retain(|s| s.id != id);
     save_servers(&servers)
 }
 
+fn get_hardware_path() -> Option<PathBuf> {
+    data_dir().map(|p| p.join("hardware.json"))
+}
+
+fn load_hardware_profiles() -> HashMap<String, HardwareProfile> {
+    let Some(path) = get_hardware_path() else {
+        return HashMap::new();
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Failed to parse hardware profiles: {}", e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_hardware_profiles(profiles: &HashMap<String, HardwareProfile>) -> Result<(), String> {
+    let Some(path) = get_hardware_path() else {
+        return Err("Could not determine config directory".to_string());
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let content = serde_json::to_string_pretty(profiles)
+        .map_err(|e| format!("Failed to serialize hardware profiles: {}", e))?;
+
+    fs::write(&path, content)
+        .map_err(|e| format!("Failed to write hardware profiles: {}", e))
+}
+
+pub fn get_hardware_profile(id: &str) -> Option<HardwareProfile> {
+    load_hardware_profiles().remove(id)
+}
+
+/// Store the profile of a server, or clear it with None
+pub fn set_hardware_profile(id: &str, profile: Option<HardwareProfile>) -> Result<(), String> {
+    let mut profiles = load_hardware_profiles();
+    match profile {
+        Some(profile) => profiles.insert(id.to_string(), profile),
+        None => profiles.remove(id),
+    };
+    save_hardware_profiles(&profiles)
+}
+
 pub fn update_server_password(id: &str, password: &str) -> Result<(), String> {
     // Save password to keyring (servers list doesn't need update)
     set_password_in_keyring(id, password)
@@ -0,0 +1,348 @@
+use crate::db::types::{ConnectionConfig, SslMode};
+use native_tls::{Certificate, Identity, TlsConnector};
+use postgres_native_tls::MakeTlsConnector;
+use std::fs;
+
+/// Build a TLS connector matching the server's sslmode and certificate paths.
+///
+/// Follows libpq semantics: `prefer`/`require` only encrypt (unless a root CA
+/// is configured, in which case `require` behaves like `verify-ca`),
+/// `verify-ca` checks the chain and `verify-full` also checks the hostname.
+pub fn make_tls_connector(config: &ConnectionConfig) -> Result<MakeTlsConnector, String> {
+    let mode = config.effective_ssl_mode();
+    let mut builder = TlsConnector::builder();
+
+    let root_cert = config.ssl_root_cert.as_deref().filter(|p| !p.is_empty());
+
+    // Without a custom CA, verify-* modes fall back to the system trust store
+    if let Some(path) = root_cert {
+        for cert in load_root_certs(path)? {
+            builder.add_root_certificate(cert);
+        }
+    }
+
+    let checks = verification(mode, root_cert.is_some());
+    builder.danger_accept_invalid_certs(!checks.chain);
+    builder.danger_accept_invalid_hostnames(!checks.hostname);
+
+    let client_cert = config.ssl_client_cert.as_deref().filter(|p| !p.is_empty());
+    let client_key = config.ssl_client_key.as_deref().filter(|p| !p.is_empty());
+
+    match (client_cert, client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            builder.identity(load_identity(cert_path, key_path)?);
+        }
+        (None, None) => {}
+        _ => {
+            return Err("Both client certificate and client key must be provided".to_string());
+        }
+    }
+
+    let connector = builder
+        .build()
+        .map_err(|e| format!("Failed to build TLS connector: {}", e))?;
+
+    Ok(MakeTlsConnector::new(connector))
+}
+
+/// Checks made on the server certificate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Verification {
+    chain: bool,
+    hostname: bool,
+}
+
+fn verification(mode: SslMode, has_root_cert: bool) -> Verification {
+    match mode {
+        SslMode::Disable | SslMode::Prefer | SslMode::Require => Verification {
+            chain: has_root_cert,
+            hostname: false,
+        },
+        SslMode::VerifyCa => Verification {
+            chain: true,
+            hostname: false,
+        },
+        SslMode::VerifyFull => Verification {
+            chain: true,
+            hostname: true,
+        },
+    }
+}
+
+/// Map our sslmode to the negotiation mode understood by the driver
+pub fn pool_ssl_mode(mode: SslMode) -> deadpool_postgres::SslMode {
+    match mode {
+        SslMode::Disable => deadpool_postgres::SslMode::Disable,
+        SslMode::Prefer => deadpool_postgres::SslMode::Prefer,
+        SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => {
+            deadpool_postgres::SslMode::Require
+        }
+    }
+}
+
+/// Turn low-level TLS failures into something actionable
+pub fn describe_error(mode: SslMode, error: &str) -> String {
+    let lower = error.to_lowercase();
+
+    if lower.contains("server does not support tls") {
+        return format!(
+            "Server does not accept SSL connections (sslmode={}). Use sslmode=disable or prefer, or enable ssl on the server",
+            mode.as_str()
+        );
+    }
+
+    if lower.contains("certificate") || lower.contains("self signed") || lower.contains("self-signed") {
+        let hint = match mode {
+            SslMode::VerifyFull => {
+                "Check that ssl_root_cert contains the issuing CA and that the host matches the certificate CN/SAN"
+            }
+            SslMode::VerifyCa => "Check that ssl_root_cert contains the issuing CA",
+            _ => "Check the configured root and client certificates",
+        };
+        return format!(
+            "TLS certificate verification failed (sslmode={}): {}. {}",
+            mode.as_str(),
+            error,
+            hint
+        );
+    }
+
+    if lower.contains("tls") || lower.contains("ssl") {
+        return format!("TLS error (sslmode={}): {}", mode.as_str(), error);
+    }
+
+    error.to_string()
+}
+
+fn load_root_certs(path: &str) -> Result<Vec<Certificate>, String> {
+    let pem = fs::read(path)
+        .map_err(|e| format!("Failed to read root certificate {}: {}", path, e))?;
+    let pem = String::from_utf8_lossy(&pem);
+
+    // native-tls parses a single certificate per call, so split bundles
+    const END_MARKER: &str = "-----END CERTIFICATE-----";
+    let certs: Vec<Certificate> = pem
+        .split_inclusive(END_MARKER)
+        .filter(|block| block.contains("-----BEGIN CERTIFICATE-----"))
+        .map(|block| {
+            Certificate::from_pem(block.trim().as_bytes())
+                .map_err(|e| format!("Invalid root certificate in {}: {}", path, e))
+        })
+        .collect::<Result<_, _>>()?;
+
+    if certs.is_empty() {
+        return Err(format!("No PEM certificates found in {}", path));
+    }
+
+    Ok(certs)
+}
+
+fn load_identity(cert_path: &str, key_path: &str) -> Result<Identity, String> {
+    let cert = fs::read(cert_path)
+        .map_err(|e| format!("Failed to read client certificate {}: {}", cert_path, e))?;
+    let key = fs::read(key_path)
+        .map_err(|e| format!("Failed to read client key {}: {}", key_path, e))?;
+
+    Identity::from_pkcs8(&cert, &key)
+        .map_err(|e| format!("Invalid client certificate/key (key must be PEM PKCS#8): {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT_BUNDLE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/tls/root_bundle.pem"
+    );
+    const CLIENT_CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/client.pem");
+    const CLIENT_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/client.key");
+    const MISSING: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/tls/missing.pem"
+    );
+
+    const MODES: [SslMode; 5] = [
+        SslMode::Disable,
+        SslMode::Prefer,
+        SslMode::Require,
+        SslMode::VerifyCa,
+        SslMode::VerifyFull,
+    ];
+
+    fn config(mode: SslMode) -> ConnectionConfig {
+        ConnectionConfig {
+            id: "test".to_string(),
+            name: "test".to_string(),
+            host: "db.example.com".to_string(),
+            port: 5432,
+            database: "postgres".to_string(),
+            username: "postgres".to_string(),
+            password: String::new(),
+            use_ssl: false,
+            ssl_mode: Some(mode),
+            ssl_root_cert: None,
+            ssl_client_cert: None,
+            ssl_client_key: None,
+            ssh_tunnel: None,
+        }
+    }
+
+    fn error(result: Result<MakeTlsConnector, String>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn sslmode_sets_certificate_checks() {
+        let checks = |chain, hostname| Verification { chain, hostname };
+        let expected = [
+            (SslMode::Disable, checks(false, false), checks(true, false)),
+            (SslMode::Prefer, checks(false, false), checks(true, false)),
+            (SslMode::Require, checks(false, false), checks(true, false)),
+            (SslMode::VerifyCa, checks(true, false), checks(true, false)),
+            (SslMode::VerifyFull, checks(true, true), checks(true, true)),
+        ];
+        for (mode, without_ca, with_ca) in expected {
+            assert_eq!(verification(mode, false), without_ca, "{:?}", mode);
+            assert_eq!(verification(mode, true), with_ca, "{:?} with root CA", mode);
+        }
+    }
+
+    #[test]
+    fn sslmode_maps_to_driver_mode() {
+        use deadpool_postgres::SslMode as Driver;
+
+        let driver: Vec<Driver> = MODES.iter().map(|mode| pool_ssl_mode(*mode)).collect();
+        assert!(matches!(
+            driver[..],
+            [
+                Driver::Disable,
+                Driver::Prefer,
+                Driver::Require,
+                Driver::Require,
+                Driver::Require
+            ]
+        ));
+    }
+
+    #[test]
+    fn use_ssl_without_sslmode_means_require() {
+        let mut config = config(SslMode::Disable);
+        config.ssl_mode = None;
+        assert_eq!(config.effective_ssl_mode(), SslMode::Disable);
+        config.use_ssl = true;
+        assert_eq!(config.effective_ssl_mode(), SslMode::Require);
+    }
+
+    #[test]
+    fn connector_builds_for_every_mode() {
+        for mode in MODES {
+            assert!(make_tls_connector(&config(mode)).is_ok(), "{:?}", mode);
+
+            let mut config = config(mode);
+            config.ssl_root_cert = Some(ROOT_BUNDLE.to_string());
+            config.ssl_client_cert = Some(CLIENT_CERT.to_string());
+            config.ssl_client_key = Some(CLIENT_KEY.to_string());
+            assert!(
+                make_tls_connector(&config).is_ok(),
+                "{:?} with certificates",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn root_bundle_is_split_into_certificates() {
+        assert_eq!(load_root_certs(ROOT_BUNDLE).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn root_cert_errors_name_the_file() {
+        let mut config = config(SslMode::VerifyFull);
+        config.ssl_root_cert = Some(MISSING.to_string());
+        let e = error(make_tls_connector(&config));
+        assert!(e.starts_with("Failed to read root certificate"), "{}", e);
+        assert!(e.contains(MISSING), "{}", e);
+
+        // A PEM file holding only a key has no certificates
+        let e = load_root_certs(CLIENT_KEY).map(|_| ()).unwrap_err();
+        assert_eq!(e, format!("No PEM certificates found in {}", CLIENT_KEY));
+    }
+
+    #[test]
+    fn empty_paths_are_ignored() {
+        let mut config = config(SslMode::VerifyCa);
+        config.ssl_root_cert = Some(String::new());
+        config.ssl_client_cert = Some(String::new());
+        config.ssl_client_key = Some(String::new());
+        assert!(make_tls_connector(&config).is_ok());
+    }
+
+    #[test]
+    fn client_cert_needs_both_files() {
+        let mut config = config(SslMode::Require);
+        config.ssl_client_cert = Some(CLIENT_CERT.to_string());
+        assert_eq!(
+            error(make_tls_connector(&config)),
+            "Both client certificate and client key must be provided"
+        );
+
+        config.ssl_client_key = Some(MISSING.to_string());
+        let e = error(make_tls_connector(&config));
+        assert!(e.starts_with("Failed to read client key"), "{}", e);
+
+        // Certificate and key swapped
+        config.ssl_client_cert = Some(CLIENT_KEY.to_string());
+        config.ssl_client_key = Some(CLIENT_CERT.to_string());
+        let e = error(make_tls_connector(&config));
+        assert!(e.starts_with("Invalid client certificate/key"), "{}", e);
+    }
+
+    #[test]
+    fn describe_error_hints() {
+        let e = describe_error(
+            SslMode::Require,
+            "error performing TLS handshake: server does not support TLS",
+        );
+        assert!(
+            e.starts_with("Server does not accept SSL connections (sslmode=require)"),
+            "{}",
+            e
+        );
+
+        let failure = "certificate verify failed: self signed certificate in certificate chain";
+        let e = describe_error(SslMode::VerifyFull, failure);
+        assert!(
+            e.starts_with("TLS certificate verification failed (sslmode=verify-full)"),
+            "{}",
+            e
+        );
+        assert!(e.contains("host matches the certificate CN/SAN"), "{}", e);
+
+        let e = describe_error(SslMode::VerifyCa, failure);
+        assert!(
+            e.ends_with("Check that ssl_root_cert contains the issuing CA"),
+            "{}",
+            e
+        );
+
+        let e = describe_error(SslMode::Require, failure);
+        assert!(
+            e.ends_with("Check the configured root and client certificates"),
+            "{}",
+            e
+        );
+
+        assert_eq!(
+            describe_error(SslMode::Prefer, "unexpected EOF during SSL handshake"),
+            "TLS error (sslmode=prefer): unexpected EOF during SSL handshake"
+        );
+        assert_eq!(
+            describe_error(SslMode::Disable, "password authentication failed"),
+            "password authentication failed"
+        );
+    }
+}
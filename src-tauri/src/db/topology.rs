@@ -0,0 +1,294 @@
+use super::connection::ConnectionManager;
+use super::metrics::{ReplicationStat, WalReceiverStat};
+use super::queries::MetricsCollector;
+use super::storage::SavedServerConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerRole {
+    Primary,
+    Standby,
+    /// Standby streaming from another standby
+    CascadingStandby,
+    /// Not connected or the probe failed
+    Unknown,
+}
+
+/// A connection listed in pg_stat_replication of a registered server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownstreamReplica {
+    pub application_name: Option<String>,
+    pub client_addr: Option<String>,
+    pub state: Option<String>,
+    pub sync_state: Option<String>,
+    pub replay_lag_bytes: Option<i64>,
+    /// Registered server this connection was matched to
+    pub server_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyNode {
+    pub server_id: String,
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub role: ServerRole,
+    pub system_identifier: Option<String>,
+    /// Where this standby streams from, as reported by its WAL receiver
+    pub upstream_host: Option<String>,
+    pub upstream_port: Option<i32>,
+    /// Registered server matching the upstream address
+    pub upstream_server_id: Option<String>,
+    pub downstream: Vec<DownstreamReplica>,
+    /// Standby whose WAL receiver is not streaming
+    pub orphaned: bool,
+    pub error: Option<String>,
+}
+
+/// Registered servers sharing one system identifier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cluster {
+    pub system_identifier: String,
+    pub primary_server_id: Option<String>,
+    /// More than one server in the cluster is out of recovery
+    pub multiple_primaries: bool,
+    pub nodes: Vec<TopologyNode>,
+    /// WAL senders of cluster members not matched to a registered server
+    /// (unregistered replicas, or tools such as pg_receivewal)
+    pub unregistered_replicas: Vec<DownstreamReplica>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterTopology {
+    pub clusters: Vec<Cluster>,
+    /// Servers that could not be probed or whose system identifier is unknown
+    pub ungrouped: Vec<TopologyNode>,
+}
+
+/// Probe result of one connected server
+struct Probe {
+    system_identifier: Option<String>,
+    in_recovery: bool,
+    replicas: Vec<ReplicationStat>,
+    receiver: Option<WalReceiverStat>,
+}
+
+pub struct TopologyDiscovery;
+
+impl TopologyDiscovery {
+    /// Group connected saved servers into clusters and work out roles
+    pub async fn discover(manager: &ConnectionManager) -> Result<ClusterTopology, String> {
+        let connected: HashSet<String> = manager.connected_server_ids().into_iter().collect();
+        let mut servers = manager.saved_server_configs();
+        servers.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+
+        let mut nodes = Vec::new();
+        let mut probes = Vec::new();
+        let mut addresses = Vec::new();
+
+        for server in &servers {
+            addresses.push(resolve(&server.host, server.port).await);
+
+            let probe = if connected.contains(&server.id) {
+                Self::probe(manager, &server.id).await
+            } else {
+                Err("Not connected".to_string())
+            };
+
+            nodes.push(TopologyNode {
+                server_id: server.id.clone(),
+                name: server.name.clone(),
+                host: server.host.clone(),
+                port: server.port,
+                role: ServerRole::Unknown,
+                system_identifier: None,
+                upstream_host: None,
+                upstream_port: None,
+                upstream_server_id: None,
+                downstream: Vec::new(),
+                orphaned: false,
+                error: probe.as_ref().err().cloned(),
+            });
+            probes.push(probe.ok());
+        }
+
+        for (node, probe) in nodes.iter_mut().zip(&probes) {
+            node.system_identifier = probe.as_ref().and_then(|p| p.system_identifier.clone());
+        }
+
+        // Upstream links from each standby's WAL receiver
+        for i in 0..nodes.len() {
+            let Some(probe) = &probes[i] else {
+                continue;
+            };
+
+            if !probe.in_recovery {
+                nodes[i].role = ServerRole::Primary;
+                continue;
+            }
+
+            nodes[i].role = ServerRole::Standby;
+            let Some(receiver) = &probe.receiver else {
+                nodes[i].orphaned = true;
+                continue;
+            };
+            nodes[i].orphaned = receiver.status != "streaming";
+            nodes[i].upstream_host = receiver.sender_host.clone();
+            nodes[i].upstream_port = receiver.sender_port;
+
+            if let Some(host) = &receiver.sender_host {
+                let sender = resolve(host, receiver.sender_port.unwrap_or(5432) as u16).await;
+                nodes[i].upstream_server_id = (0..nodes.len())
+                    .find(|&j| {
+                        j != i
+                            && same_cluster(&nodes[i], &nodes[j])
+                            && receiver
+                                .sender_port
+                                .is_none_or(|p| p == servers[j].port as i32)
+                            && !addresses[j].is_disjoint(&sender)
+                    })
+                    .map(|j| servers[j].id.clone());
+            }
+        }
+
+        // Roles of standbys fed by other standbys
+        for node in nodes.iter_mut() {
+            let upstream_is_standby = node
+                .upstream_server_id
+                .as_ref()
+                .and_then(|id| probes[index_of(&servers, id)?].as_ref())
+                .is_some_and(|p| p.in_recovery);
+            if upstream_is_standby {
+                node.role = ServerRole::CascadingStandby;
+            }
+        }
+
+        // Downstream connections, matched to registered standbys
+        for i in 0..nodes.len() {
+            let Some(probe) = &probes[i] else {
+                continue;
+            };
+            let downstream = probe
+                .replicas
+                .iter()
+                .map(|replica| {
+                    let client: Option<IpAddr> =
+                        replica.client_addr.as_deref().and_then(|a| a.parse().ok());
+                    let server_id = (0..nodes.len())
+                        .filter(|&j| j != i && same_cluster(&nodes[i], &nodes[j]))
+                        .filter(|&j| match &nodes[j].upstream_server_id {
+                            Some(upstream) => *upstream == servers[i].id,
+                            None => nodes[j].role != ServerRole::Primary,
+                        })
+                        .find(|&j| client.is_some_and(|c| addresses[j].contains(&c)))
+                        .map(|j| servers[j].id.clone());
+
+                    DownstreamReplica {
+                        application_name: replica.application_name.clone(),
+                        client_addr: replica.client_addr.clone(),
+                        state: replica.state.clone(),
+                        sync_state: replica.sync_state.clone(),
+                        replay_lag_bytes: replica.replay_lag_bytes,
+                        server_id,
+                    }
+                })
+                .collect();
+            nodes[i].downstream = downstream;
+        }
+
+        Ok(group(nodes))
+    }
+
+    async fn probe(manager: &ConnectionManager, server_id: &str) -> Result<Probe, String> {
+        let (system_identifier, in_recovery) = {
+            let client = manager.get_client(server_id).await?;
+            let row = client
+                .query_one(
+                    "SELECT (SELECT system_identifier::text FROM pg_control_system()), pg_is_in_recovery()",
+                    &[],
+                )
+                .await
+                .map_err(|e| format!("Failed to query system identifier: {}", e))?;
+            (row.get(0), row.get(1))
+        };
+
+        let replicas = MetricsCollector::get_replication_stats(manager, server_id).await?;
+        let receiver = if in_recovery {
+            MetricsCollector::get_wal_receiver(manager, server_id).await?
+        } else {
+            None
+        };
+
+        Ok(Probe {
+            system_identifier,
+            in_recovery,
+            replicas,
+            receiver,
+        })
+    }
+}
+
+fn same_cluster(a: &TopologyNode, b: &TopologyNode) -> bool {
+    a.system_identifier.is_some() && a.system_identifier == b.system_identifier
+}
+
+fn index_of(servers: &[SavedServerConfig], id: &str) -> Option<usize> {
+    servers.iter().position(|s| s.id == id)
+}
+
+/// IP addresses of a host; literal addresses parse without DNS
+async fn resolve(host: &str, port: u16) -> HashSet<IpAddr> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return HashSet::from([ip]);
+    }
+
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => addrs.map(|a| a.ip()).collect(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+fn group(nodes: Vec<TopologyNode>) -> ClusterTopology {
+    let mut clusters: BTreeMap<String, Vec<TopologyNode>> = BTreeMap::new();
+    let mut ungrouped = Vec::new();
+
+    for node in nodes {
+        match node.system_identifier.clone() {
+            Some(id) => clusters.entry(id).or_default().push(node),
+            None => ungrouped.push(node),
+        }
+    }
+
+    let clusters = clusters
+        .into_iter()
+        .map(|(system_identifier, nodes)| {
+            let primaries: Vec<&TopologyNode> = nodes
+                .iter()
+                .filter(|n| n.role == ServerRole::Primary)
+                .collect();
+
+            let unregistered_replicas = nodes
+                .iter()
+                .flat_map(|n| n.downstream.iter())
+                .filter(|r| r.server_id.is_none())
+                .cloned()
+                .collect();
+
+            Cluster {
+                system_identifier,
+                primary_server_id: primaries.first().map(|n| n.server_id.clone()),
+                multiple_primaries: primaries.len() > 1,
+                unregistered_replicas,
+                nodes,
+            }
+        })
+        .collect();
+
+    ClusterTopology {
+        clusters,
+        ungrouped,
+    }
+}
@@ -0,0 +1,342 @@
+use super::config::{ConfigCollector, HardwareInfo};
+use super::connection::ConnectionManager;
+use serde::{Deserialize, Serialize};
+
+const KB_PER_MB: i64 = 1024;
+const KB_PER_GB: i64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageType {
+    Ssd,
+    Hdd,
+    /// Network or SAN storage
+    San,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkloadProfile {
+    /// Many short transactions
+    Oltp,
+    /// Web application: mostly simple reads, many connections
+    Web,
+    /// Few large analytical queries
+    DataWarehouse,
+    Mixed,
+}
+
+impl WorkloadProfile {
+    fn default_connections(&self) -> i32 {
+        match self {
+            WorkloadProfile::Oltp => 300,
+            WorkloadProfile::Web => 200,
+            WorkloadProfile::DataWarehouse => 40,
+            WorkloadProfile::Mixed => 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TuningRequest {
+    pub storage_type: StorageType,
+    pub workload: WorkloadProfile,
+    /// Defaults to a typical value for the workload
+    #[serde(default)]
+    pub max_connections: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecommendedSetting {
+    pub name: String,
+    pub value: String,
+    /// As reported by current_setting(); None if the server was not asked
+    pub current_value: Option<String>,
+    pub requires_restart: bool,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TuningRecommendation {
+    pub hardware: HardwareInfo,
+    pub request: TuningRequest,
+    pub settings: Vec<RecommendedSetting>,
+    pub alter_system: String,
+    pub conf_fragment: String,
+}
+
+/// pgtune-style configuration generator
+pub struct ConfigTuner;
+
+impl ConfigTuner {
+    /// Recommendation for a connected server, compared with its current
+    /// settings; parameters the server does not know are left out
+    pub async fn generate(
+        manager: &ConnectionManager,
+        server_id: &str,
+        request: &TuningRequest,
+    ) -> Result<TuningRecommendation, String> {
+        let capabilities = manager.capabilities(server_id).await?;
+        let hardware = ConfigCollector::get_hardware_info(manager, server_id).await?;
+        let mut settings = Self::recommend(&hardware, capabilities.version_num, request);
+
+        let client = manager.get_client(server_id).await?;
+        let names: Vec<String> = settings.iter().map(|s| s.name.clone()).collect();
+        let rows = client
+            .query(
+                "SELECT name, current_setting(name), context FROM pg_settings WHERE name = ANY($1)",
+                &[&names],
+            )
+            .await
+            .map_err(|e| format!("Failed to query current settings: {}", e))?;
+
+        settings.retain_mut(|setting| {
+            let Some(row) = rows
+                .iter()
+                .find(|row| row.get::<_, &str>(0) == setting.name)
+            else {
+                return false;
+            };
+            setting.current_value = Some(row.get(1));
+            setting.requires_restart = row.get::<_, &str>(2) == "postmaster";
+            true
+        });
+
+        Ok(Self::render(hardware, request.clone(), settings))
+    }
+
+    /// Full recommended set for the given hardware, version and workload
+    pub fn recommend(
+        hardware: &HardwareInfo,
+        version_num: i32,
+        request: &TuningRequest,
+    ) -> Vec<RecommendedSetting> {
+        let workload = request.workload;
+        let memory_kb = hardware.total_memory_mb.max(1) * KB_PER_MB;
+        let cpus = hardware.cpu_cores.max(1);
+        let connections = request
+            .max_connections
+            .unwrap_or_else(|| workload.default_connections())
+            .max(1);
+
+        let mut settings = Vec::new();
+        let mut set = |name: &str, value: String, reason: &str| {
+            settings.push(RecommendedSetting {
+                name: name.to_string(),
+                value,
+                current_value: None,
+                requires_restart: false,
+                reason: reason.to_string(),
+            });
+        };
+
+        set(
+            "max_connections",
+            connections.to_string(),
+            "Expected connections; use a pooler rather than raising this further",
+        );
+
+        let shared_buffers_kb = memory_kb / 4;
+        set(
+            "shared_buffers",
+            format_kb(shared_buffers_kb),
+            "25% of RAM; the OS page cache holds the rest",
+        );
+
+        set(
+            "effective_cache_size",
+            format_kb(memory_kb * 3 / 4),
+            "75% of RAM: shared_buffers plus the expected OS cache",
+        );
+
+        let maintenance_kb = match workload {
+            WorkloadProfile::DataWarehouse => memory_kb / 8,
+            _ => memory_kb / 16,
+        }
+        .min(2 * KB_PER_GB);
+        set(
+            "maintenance_work_mem",
+            format_kb(maintenance_kb),
+            "Faster VACUUM and CREATE INDEX; capped at 2GB",
+        );
+
+        // 3% of shared_buffers, at most one 16MB WAL segment
+        let wal_buffers_kb = match shared_buffers_kb * 3 / 100 {
+            kb if kb > 14 * KB_PER_MB => 16 * KB_PER_MB,
+            kb => kb.max(32),
+        };
+        set(
+            "wal_buffers",
+            format_kb(wal_buffers_kb),
+            "3% of shared_buffers, up to one WAL segment",
+        );
+
+        let (min_wal, max_wal) = match workload {
+            WorkloadProfile::Oltp => (2 * KB_PER_GB, 8 * KB_PER_GB),
+            WorkloadProfile::DataWarehouse => (4 * KB_PER_GB, 16 * KB_PER_GB),
+            WorkloadProfile::Web | WorkloadProfile::Mixed => (KB_PER_GB, 4 * KB_PER_GB),
+        };
+        set(
+            "min_wal_size",
+            format_kb(min_wal),
+            "Keeps WAL segments recycled instead of recreated",
+        );
+        set(
+            "max_wal_size",
+            format_kb(max_wal),
+            "Spaces checkpoints out under write load",
+        );
+
+        set(
+            "checkpoint_completion_target",
+            "0.9".to_string(),
+            "Spreads checkpoint writes over the interval",
+        );
+
+        set(
+            "default_statistics_target",
+            match workload {
+                WorkloadProfile::DataWarehouse => "500",
+                _ => "100",
+            }
+            .to_string(),
+            "Larger samples help the planner on analytical queries",
+        );
+
+        set(
+            "random_page_cost",
+            match request.storage_type {
+                StorageType::Hdd => "4",
+                StorageType::Ssd | StorageType::San => "1.1",
+            }
+            .to_string(),
+            "Random reads cost close to sequential reads on flash and SAN storage",
+        );
+
+        // Prefetching needs posix_fadvise, which only Linux builds use
+        if hardware.os_type == "Linux" {
+            set(
+                "effective_io_concurrency",
+                match request.storage_type {
+                    StorageType::Hdd => "2",
+                    StorageType::Ssd => "200",
+                    StorageType::San => "300",
+                }
+                .to_string(),
+                "Concurrent prefetch requests the storage can serve",
+            );
+        }
+
+        let mut workers_per_gather = 0;
+        if cpus >= 4 {
+            workers_per_gather = match workload {
+                WorkloadProfile::DataWarehouse => (cpus + 1) / 2,
+                _ => ((cpus + 1) / 2).min(4),
+            };
+            set(
+                "max_worker_processes",
+                cpus.to_string(),
+                "One background worker per CPU",
+            );
+            set(
+                "max_parallel_workers_per_gather",
+                workers_per_gather.to_string(),
+                "Half the CPUs for one query, at most 4 outside data warehouses",
+            );
+            if version_num >= 100000 {
+                set(
+                    "max_parallel_workers",
+                    cpus.to_string(),
+                    "All CPUs may run parallel workers",
+                );
+            }
+            if version_num >= 110000 {
+                set(
+                    "max_parallel_maintenance_workers",
+                    ((cpus + 1) / 2).min(4).to_string(),
+                    "Parallel CREATE INDEX and VACUUM",
+                );
+            }
+        }
+
+        // Leave room for every connection and worker running a few sorts at once
+        let work_mem_kb = (memory_kb - shared_buffers_kb)
+            / ((connections as i64 + cpus as i64) * 3)
+            / workers_per_gather.max(1) as i64
+            / match workload {
+                WorkloadProfile::DataWarehouse | WorkloadProfile::Mixed => 2,
+                WorkloadProfile::Oltp | WorkloadProfile::Web => 1,
+            };
+        set(
+            "work_mem",
+            format_kb(work_mem_kb.max(64)),
+            "Per sort or hash node; sized so all connections fit in RAM",
+        );
+
+        if memory_kb >= 32 * KB_PER_GB {
+            set(
+                "huge_pages",
+                "try".to_string(),
+                "Large shared memory benefits from huge pages when the OS provides them",
+            );
+        }
+
+        settings
+    }
+
+    /// Wrap settings with ALTER SYSTEM statements and a postgresql.conf
+    /// fragment
+    pub fn render(
+        hardware: HardwareInfo,
+        request: TuningRequest,
+        settings: Vec<RecommendedSetting>,
+    ) -> TuningRecommendation {
+        let alter_system = settings
+            .iter()
+            .map(|s| format!("ALTER SYSTEM SET {} = '{}';", s.name, s.value))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut conf_fragment = format!(
+            "# {:?} workload, {:?} storage, {} CPUs, {} MB RAM\n",
+            request.workload, request.storage_type, hardware.cpu_cores, hardware.total_memory_mb
+        );
+        for setting in &settings {
+            let value = if setting.value.parse::<f64>().is_ok() {
+                setting.value.clone()
+            } else {
+                format!("'{}'", setting.value)
+            };
+            conf_fragment.push_str(&format!(
+                "{} = {}{}\n",
+                setting.name,
+                value,
+                if setting.requires_restart {
+                    "\t# requires restart"
+                } else {
+                    ""
+                }
+            ));
+        }
+
+        TuningRecommendation {
+            hardware,
+            request,
+            settings,
+            alter_system,
+            conf_fragment,
+        }
+    }
+}
+
+/// Memory size as whole GB when exact, else whole MB (rounded down), e.g.
+/// "4GB", "3917MB" or "640kB"
+fn format_kb(kb: i64) -> String {
+    if kb >= KB_PER_GB && kb % KB_PER_GB == 0 {
+        format!("{}GB", kb / KB_PER_GB)
+    } else if kb >= KB_PER_MB {
+        format!("{}MB", kb / KB_PER_MB)
+    } else {
+        format!("{}kB", kb)
+    }
+}
@@ -0,0 +1,399 @@
+use crate::db::types::{KnownHostsPolicy, SshTunnelConfig};
+use ssh2::{Channel, CheckResult, KnownHostFileKind, Session};
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const KEEPALIVE_INTERVAL_SECS: u32 = 30;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const IDLE_POLL: Duration = Duration::from_millis(5);
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Local port forwarded to a database host through an SSH jump host.
+///
+/// Connections accepted on `127.0.0.1:<local_port>` are relayed over
+/// `direct-tcpip` channels by a background thread, which sends keepalives and
+/// re-establishes the SSH session if it drops. Dropping the tunnel signals
+/// the thread to stop without waiting for it, since it may be inside a
+/// blocking SSH connect; the thread closes the listener when it exits.
+pub struct SshTunnel {
+    local_port: u16,
+    shutdown: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+}
+
+impl SshTunnel {
+    /// Open the tunnel. Blocks until the SSH session is authenticated, so
+    /// call it from a blocking context.
+    pub fn open(config: &SshTunnelConfig, target_host: &str, target_port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))
+            .map_err(|e| format!("Failed to bind local tunnel port: {}", e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure local tunnel port: {}", e))?;
+        let local_port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read local tunnel port: {}", e))?
+            .port();
+
+        // Connect once up front so auth and host key errors reach the caller
+        let session = connect_session(config)?;
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let connected = Arc::new(AtomicBool::new(true));
+
+        let forwarder = Forwarder {
+            config: config.clone(),
+            target_host: target_host.to_string(),
+            target_port,
+            listener,
+            shutdown: shutdown.clone(),
+            connected: connected.clone(),
+        };
+
+        thread::Builder::new()
+            .name(format!("ssh-tunnel-{}", local_port))
+            .spawn(move || forwarder.run(session))
+            .map_err(|e| format!("Failed to start SSH tunnel thread: {}", e))?;
+
+        Ok(SshTunnel {
+            local_port,
+            shutdown,
+            connected,
+        })
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    /// Whether the SSH session is currently up (false while reconnecting)
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Forwarder {
+    config: SshTunnelConfig,
+    target_host: String,
+    target_port: u16,
+    listener: TcpListener,
+    shutdown: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+}
+
+impl Forwarder {
+    fn run(self, initial: Session) {
+        let mut session = initial;
+
+        loop {
+            self.connected.store(true, Ordering::Relaxed);
+            self.pump(&session);
+            self.connected.store(false, Ordering::Relaxed);
+
+            // Keep the listener so existing pool settings stay valid
+            session = loop {
+                if !self.wait(RECONNECT_DELAY) {
+                    return;
+                }
+                match connect_session(&self.config) {
+                    Ok(session) => break session,
+                    Err(e) => eprintln!("SSH tunnel reconnect failed: {}", e),
+                }
+            };
+        }
+    }
+
+    /// Relay traffic until shutdown or until the SSH session fails
+    fn pump(&self, session: &Session) {
+        session.set_blocking(false);
+
+        let mut links: Vec<Link> = Vec::new();
+        let mut next_keepalive = Instant::now();
+
+        while !self.shutdown.load(Ordering::Relaxed) {
+            let mut active = false;
+
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    active = true;
+                    match self.open_link(session, stream) {
+                        Ok(link) => links.push(link),
+                        Err(e) => {
+                            eprintln!("SSH tunnel failed to open channel: {}", e);
+                            // Check the session is still alive right away
+                            next_keepalive = Instant::now();
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => eprintln!("SSH tunnel accept error: {}", e),
+            }
+
+            links.retain_mut(|link| match link.pump() {
+                Ok(moved) => {
+                    active |= moved;
+                    true
+                }
+                Err(_) => false,
+            });
+
+            if Instant::now() >= next_keepalive {
+                match session.keepalive_send() {
+                    Ok(secs) => {
+                        next_keepalive = Instant::now() + Duration::from_secs(secs.max(1) as u64);
+                    }
+                    Err(e) => {
+                        let e = io::Error::from(e);
+                        if e.kind() != ErrorKind::WouldBlock {
+                            eprintln!("SSH tunnel to {} lost: {}", self.config.host, e);
+                            return;
+                        }
+                        next_keepalive = Instant::now() + Duration::from_secs(1);
+                    }
+                }
+            }
+
+            if !active {
+                thread::sleep(IDLE_POLL);
+            }
+        }
+    }
+
+    fn open_link(&self, session: &Session, stream: TcpStream) -> Result<Link, String> {
+        session.set_blocking(true);
+        let channel = session.channel_direct_tcpip(&self.target_host, self.target_port, None);
+        session.set_blocking(false);
+
+        let channel = channel.map_err(|e| {
+            format!("{}:{} via {}: {}", self.target_host, self.target_port, self.config.host, e)
+        })?;
+
+        stream
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure tunnel socket: {}", e))?;
+
+        Ok(Link {
+            stream,
+            channel,
+            to_remote: Vec::new(),
+            to_local: Vec::new(),
+        })
+    }
+
+    /// Sleep in small steps; returns false if shutdown was requested
+    fn wait(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline {
+            if self.shutdown.load(Ordering::Relaxed) {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        !self.shutdown.load(Ordering::Relaxed)
+    }
+}
+
+/// One forwarded client connection
+struct Link {
+    stream: TcpStream,
+    channel: Channel,
+    to_remote: Vec<u8>,
+    to_local: Vec<u8>,
+}
+
+impl Link {
+    /// Move pending data in both directions. Returns whether anything moved,
+    /// or an error once either side is closed.
+    fn pump(&mut self) -> io::Result<bool> {
+        let mut moved = false;
+        let mut buf = [0u8; BUFFER_SIZE];
+
+        if self.to_remote.is_empty() {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "local side closed")),
+                Ok(n) => {
+                    self.to_remote.extend_from_slice(&buf[..n]);
+                    moved = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if !self.to_remote.is_empty() {
+            match self.channel.write(&self.to_remote) {
+                Ok(n) => {
+                    self.to_remote.drain(..n);
+                    moved |= n > 0;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if self.to_local.is_empty() {
+            match self.channel.read(&mut buf) {
+                Ok(0) if self.channel.eof() => {
+                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "remote side closed"));
+                }
+                Ok(n) => {
+                    self.to_local.extend_from_slice(&buf[..n]);
+                    moved |= n > 0;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if !self.to_local.is_empty() {
+            match self.stream.write(&self.to_local) {
+                Ok(n) => {
+                    self.to_local.drain(..n);
+                    moved |= n > 0;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(moved)
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        let _ = self.channel.close();
+    }
+}
+
+fn connect_session(config: &SshTunnelConfig) -> Result<Session, String> {
+    let addr = (config.host.as_str(), config.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve SSH host {}: {}", config.host, e))?
+        .next()
+        .ok_or_else(|| format!("SSH host {} has no addresses", config.host))?;
+
+    let tcp = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Failed to connect to SSH host {}:{}: {}", config.host, config.port, e))?;
+
+    let mut session = Session::new().map_err(|e| format!("Failed to create SSH session: {}", e))?;
+    session.set_tcp_stream(tcp);
+    session.set_timeout(CONNECT_TIMEOUT.as_millis() as u32);
+    session
+        .handshake()
+        .map_err(|e| format!("SSH handshake with {} failed: {}", config.host, e))?;
+
+    verify_host_key(&session, config)?;
+    authenticate(&session, config)?;
+
+    session.set_keepalive(true, KEEPALIVE_INTERVAL_SECS);
+
+    Ok(session)
+}
+
+fn verify_host_key(session: &Session, config: &SshTunnelConfig) -> Result<(), String> {
+    if config.known_hosts_policy == KnownHostsPolicy::Ignore {
+        return Ok(());
+    }
+
+    let (key, key_type) = session
+        .host_key()
+        .ok_or("SSH server did not present a host key")?;
+
+    let path = known_hosts_path(config)?;
+    let mut known_hosts = session
+        .known_hosts()
+        .map_err(|e| format!("Failed to initialize known_hosts: {}", e))?;
+
+    if path.exists() {
+        known_hosts
+            .read_file(&path, KnownHostFileKind::OpenSSH)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    }
+
+    match known_hosts.check_port(&config.host, config.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(format!(
+            "SSH host key for {} does not match {}. The host key may have changed or the connection is being intercepted",
+            config.host,
+            path.display()
+        )),
+        CheckResult::NotFound => match config.known_hosts_policy {
+            KnownHostsPolicy::AcceptNew => {
+                let entry = if config.port == 22 {
+                    config.host.clone()
+                } else {
+                    format!("[{}]:{}", config.host, config.port)
+                };
+                known_hosts
+                    .add(&entry, key, "added by pg-dashboard", key_type.into())
+                    .map_err(|e| format!("Failed to record SSH host key: {}", e))?;
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+                }
+                known_hosts
+                    .write_file(&path, KnownHostFileKind::OpenSSH)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+            }
+            _ => Err(format!(
+                "SSH host {} is not in {}. Add its key or use the accept-new policy",
+                config.host,
+                path.display()
+            )),
+        },
+        CheckResult::Failure => Err(format!("Failed to check SSH host key for {}", config.host)),
+    }
+}
+
+fn authenticate(session: &Session, config: &SshTunnelConfig) -> Result<(), String> {
+    match config.key_path.as_deref().filter(|p| !p.is_empty()) {
+        Some(key_path) => {
+            let passphrase = Some(config.passphrase.as_str()).filter(|p| !p.is_empty());
+            session
+                .userauth_pubkey_file(&config.username, None, Path::new(key_path), passphrase)
+                .map_err(|e| {
+                    format!(
+                        "SSH key authentication failed for {}@{}: {}",
+                        config.username, config.host, e
+                    )
+                })?;
+        }
+        None => {
+            session.userauth_agent(&config.username).map_err(|e| {
+                format!(
+                    "SSH agent authentication failed for {}@{}: {}",
+                    config.username, config.host, e
+                )
+            })?;
+        }
+    }
+
+    if !session.authenticated() {
+        return Err(format!("SSH authentication failed for {}@{}", config.username, config.host));
+    }
+
+    Ok(())
+}
+
+fn known_hosts_path(config: &SshTunnelConfig) -> Result<PathBuf, String> {
+    match config.known_hosts_path.as_deref().filter(|p| !p.is_empty()) {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => dirs::home_dir()
+            .map(|home| home.join(".ssh").join("known_hosts"))
+            .ok_or_else(|| "Could not determine home directory for known_hosts".to_string()),
+    }
+}
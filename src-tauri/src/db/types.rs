@@ -1,5 +1,60 @@
 use serde::{Deserialize, Serialize};
 
+/// libpq-compatible sslmode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl SslMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+}
+
+/// How the jump host's SSH key is checked against known_hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum KnownHostsPolicy {
+    /// Refuse unknown and mismatching hosts
+    #[default]
+    Strict,
+    /// Record unknown hosts on first use, refuse mismatches
+    AcceptNew,
+    /// Skip host key verification entirely
+    Ignore,
+}
+
+/// SSH jump host used to reach a database that is not directly routable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshTunnelConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// Private key file; when absent the SSH agent is used
+    #[serde(default)]
+    pub key_path: Option<String>,
+    /// Key passphrase (stored in OS keyring, never written to disk)
+    #[serde(default, skip_serializing)]
+    pub passphrase: String,
+    #[serde(default)]
+    pub known_hosts_policy: KnownHostsPolicy,
+    /// Defaults to ~/.ssh/known_hosts
+    #[serde(default)]
+    pub known_hosts_path: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionConfig {
     pub id: String,
@@ -11,6 +66,31 @@ pub struct ConnectionConfig {
     #[serde(skip_serializing)]
     pub password: String,
     pub use_ssl: bool,
+    /// Explicit sslmode; when absent it is derived from `use_ssl`
+    #[serde(default)]
+    pub ssl_mode: Option<SslMode>,
+    /// Path to a PEM file with trusted root certificates
+    #[serde(default)]
+    pub ssl_root_cert: Option<String>,
+    /// Path to a PEM client certificate
+    #[serde(default)]
+    pub ssl_client_cert: Option<String>,
+    /// Path to a PEM (PKCS#8) client private key
+    #[serde(default)]
+    pub ssl_client_key: Option<String>,
+    /// Connect through an SSH jump host
+    #[serde(default)]
+    pub ssh_tunnel: Option<SshTunnelConfig>,
+}
+
+impl ConnectionConfig {
+    pub fn effective_ssl_mode(&self) -> SslMode {
+        match self.ssl_mode {
+            Some(mode) => mode,
+            None if self.use_ssl => SslMode::Require,
+            None => SslMode::Disable,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
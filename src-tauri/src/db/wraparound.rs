@@ -0,0 +1,274 @@
+use super::analyzer::{IssueSeverity, PerformanceIssue};
+use super::connection::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Age at which the server refuses to assign new XIDs (2^31 minus the
+/// 3M safety margin)
+const WRAPAROUND_LIMIT: i64 = 2_147_483_647 - 3_000_000;
+
+/// Ages past this share of the wraparound limit are critical regardless of
+/// autovacuum settings
+const CRITICAL_LIMIT_RATIO: f64 = 0.75;
+
+/// Tables reported per server, oldest relfrozenxid first
+const TABLE_LIMIT: i64 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseXidAge {
+    pub datname: String,
+    pub xid_age: i64,
+    pub mxid_age: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableXidAge {
+    /// Schema-qualified name, in the connected database
+    pub relation: String,
+    pub xid_age: i64,
+    pub mxid_age: i64,
+    pub last_autovacuum: Option<String>,
+    pub last_vacuum: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WraparoundReport {
+    pub autovacuum_freeze_max_age: i64,
+    pub autovacuum_multixact_freeze_max_age: i64,
+    pub databases: Vec<DatabaseXidAge>,
+    pub tables: Vec<TableXidAge>,
+    /// XIDs consumed per second since the previous report; None on the
+    /// first call
+    pub xid_rate_per_sec: Option<f64>,
+    /// Until the oldest database passes autovacuum_freeze_max_age
+    pub days_until_forced_vacuum: Option<f64>,
+    /// Until the server stops assigning XIDs
+    pub days_until_wraparound: Option<f64>,
+}
+
+/// Last observed XID counter per server, used for the consumption rate
+#[derive(Clone, Default)]
+pub struct XidRateTracker {
+    previous: Arc<Mutex<HashMap<String, (Instant, i64)>>>,
+}
+
+impl XidRateTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the current XID and return the rate since the previous sample
+    fn observe(&self, server_id: &str, xid: i64) -> Option<f64> {
+        let now = Instant::now();
+        let mut previous = self.previous.lock().unwrap();
+
+        let rate = previous.get(server_id).and_then(|(at, prev_xid)| {
+            let secs = now.duration_since(*at).as_secs_f64();
+            (secs > 0.0 && xid >= *prev_xid).then(|| (xid - prev_xid) as f64 / secs)
+        });
+
+        previous.insert(server_id.to_string(), (now, xid));
+        rate
+    }
+
+    pub fn forget(&self, server_id: &str) {
+        self.previous.lock().unwrap().remove(server_id);
+    }
+}
+
+pub struct WraparoundAnalyzer;
+
+impl WraparoundAnalyzer {
+    pub async fn get_report(
+        manager: &ConnectionManager,
+        server_id: &str,
+        tracker: &XidRateTracker,
+    ) -> Result<WraparoundReport, String> {
+        let client = manager.get_client(server_id).await?;
+
+        // txid_current() would burn an XID; the snapshot xmax does not
+        let row = client
+            .query_one(
+                r#"
+                SELECT
+                    current_setting('autovacuum_freeze_max_age')::bigint,
+                    current_setting('autovacuum_multixact_freeze_max_age')::bigint,
+                    txid_snapshot_xmax(txid_current_snapshot())
+                "#,
+                &[],
+            )
+            .await
+            .map_err(|e| format!("Failed to query freeze settings: {}", e))?;
+
+        let freeze_max_age: i64 = row.get(0);
+        let multixact_freeze_max_age: i64 = row.get(1);
+        let current_xid: i64 = row.get(2);
+
+        let rows = client
+            .query(
+                r#"
+                SELECT datname::text, age(datfrozenxid)::bigint, mxid_age(datminmxid)::bigint
+                FROM pg_database
+                WHERE datallowconn
+                ORDER BY age(datfrozenxid) DESC
+                "#,
+                &[],
+            )
+            .await
+            .map_err(|e| format!("Failed to query database XID age: {}", e))?;
+
+        let databases: Vec<DatabaseXidAge> = rows
+            .iter()
+            .map(|row| DatabaseXidAge {
+                datname: row.get(0),
+                xid_age: row.get(1),
+                mxid_age: row.get(2),
+            })
+            .collect();
+
+        let rows = client
+            .query(
+                r#"
+                SELECT
+                    c.oid::regclass::text,
+                    age(c.relfrozenxid)::bigint,
+                    mxid_age(c.relminmxid)::bigint,
+                    s.last_autovacuum::text,
+                    s.last_vacuum::text
+                FROM pg_class c
+                LEFT JOIN pg_stat_all_tables s ON s.relid = c.oid
+                WHERE c.relkind IN ('r', 'm', 't')
+                ORDER BY age(c.relfrozenxid) DESC
+                LIMIT $1
+                "#,
+                &[&TABLE_LIMIT],
+            )
+            .await
+            .map_err(|e| format!("Failed to query table XID age: {}", e))?;
+
+        let tables = rows
+            .iter()
+            .map(|row| TableXidAge {
+                relation: row.get(0),
+                xid_age: row.get(1),
+                mxid_age: row.get(2),
+                last_autovacuum: row.get(3),
+                last_vacuum: row.get(4),
+            })
+            .collect();
+
+        let xid_rate_per_sec = tracker.observe(server_id, current_xid);
+        let oldest = databases.iter().map(|d| d.xid_age).max().unwrap_or(0);
+        let days_until = |limit: i64| {
+            xid_rate_per_sec
+                .filter(|rate| *rate > 0.0)
+                .map(|rate| (limit - oldest).max(0) as f64 / rate / 86400.0)
+        };
+
+        Ok(WraparoundReport {
+            autovacuum_freeze_max_age: freeze_max_age,
+            autovacuum_multixact_freeze_max_age: multixact_freeze_max_age,
+            days_until_forced_vacuum: days_until(freeze_max_age),
+            days_until_wraparound: days_until(WRAPAROUND_LIMIT),
+            xid_rate_per_sec,
+            databases,
+            tables,
+        })
+    }
+
+    /// Turn a report into performance issues for databases and tables whose
+    /// XID or multixact age is approaching the freeze limits
+    pub fn issues(report: &WraparoundReport) -> Vec<PerformanceIssue> {
+        let mut issues = Vec::new();
+
+        let forecast = match (report.days_until_forced_vacuum, report.days_until_wraparound) {
+            (Some(forced), Some(wrap)) => format!(
+                "At {:.0} XIDs/s: {} until forced anti-wraparound vacuum, {:.1} days until wraparound shutdown",
+                report.xid_rate_per_sec.unwrap_or(0.0),
+                if forced > 0.0 {
+                    format!("{:.1} days", forced)
+                } else {
+                    "already past".to_string()
+                },
+                wrap
+            ),
+            _ => "XID consumption rate not known yet (needs a second sample)".to_string(),
+        };
+
+        for db in &report.databases {
+            if let Some(severity) = Self::severity(db.xid_age, report.autovacuum_freeze_max_age) {
+                issues.push(PerformanceIssue {
+                    issue_type: "Transaction ID Wraparound".to_string(),
+                    severity,
+                    description: format!(
+                        "Database {} has XID age {} ({:.1}% of wraparound, autovacuum_freeze_max_age {})",
+                        db.datname,
+                        db.xid_age,
+                        db.xid_age as f64 / WRAPAROUND_LIMIT as f64 * 100.0,
+                        report.autovacuum_freeze_max_age
+                    ),
+                    recommendation: "Run VACUUM (FREEZE) on the oldest tables and check for long-running transactions, abandoned replication slots or prepared transactions holding back the xmin horizon".to_string(),
+                    details: Some(forecast.clone()),
+                    object: Some(db.datname.clone()),
+                });
+            }
+
+            if let Some(severity) =
+                Self::severity(db.mxid_age, report.autovacuum_multixact_freeze_max_age)
+            {
+                issues.push(PerformanceIssue {
+                    issue_type: "Multixact Wraparound".to_string(),
+                    severity,
+                    description: format!(
+                        "Database {} has multixact age {} (autovacuum_multixact_freeze_max_age {})",
+                        db.datname, db.mxid_age, report.autovacuum_multixact_freeze_max_age
+                    ),
+                    recommendation: "Run VACUUM (FREEZE) on tables with the oldest relminmxid"
+                        .to_string(),
+                    details: None,
+                    object: Some(db.datname.clone()),
+                });
+            }
+        }
+
+        for table in &report.tables {
+            if let Some(severity) = Self::severity(table.xid_age, report.autovacuum_freeze_max_age)
+            {
+                issues.push(PerformanceIssue {
+                    issue_type: "Transaction ID Wraparound".to_string(),
+                    severity,
+                    description: format!(
+                        "Table {} has XID age {} (autovacuum_freeze_max_age {})",
+                        table.relation, table.xid_age, report.autovacuum_freeze_max_age
+                    ),
+                    recommendation: format!("VACUUM (FREEZE, VERBOSE) {};", table.relation),
+                    details: Some(format!(
+                        "Last autovacuum: {}, last vacuum: {}",
+                        table.last_autovacuum.as_deref().unwrap_or("never"),
+                        table.last_vacuum.as_deref().unwrap_or("never")
+                    )),
+                    object: Some(table.relation.clone()),
+                });
+            }
+        }
+
+        issues
+    }
+
+    /// Critical near the hard limit or when well past the freeze age,
+    /// Warning once anti-wraparound vacuums are due, Info when close to it
+    fn severity(age: i64, freeze_max_age: i64) -> Option<IssueSeverity> {
+        if age as f64 >= WRAPAROUND_LIMIT as f64 * CRITICAL_LIMIT_RATIO || age >= freeze_max_age * 2
+        {
+            Some(IssueSeverity::Critical)
+        } else if age >= freeze_max_age {
+            Some(IssueSeverity::Warning)
+        } else if age as f64 >= freeze_max_age as f64 * 0.8 {
+            Some(IssueSeverity::Info)
+        } else {
+            None
+        }
+    }
+}
@@ -1,16 +1,43 @@
 mod db;
 mod commands;
 
-use db::ConnectionManager;
+use db::{
+    ConnectionManager, MetricsSampler, ProgressTracker, RateEngine, RuleRegistry,
+    StatementSnapshots, XidRateTracker, IssueHistory, ServerData,
+};
 use commands::*;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let connection_manager = ConnectionManager::new();
+    let metrics_sampler = MetricsSampler::new();
+    let server_data = ServerData {
+        sampler: metrics_sampler.clone(),
+        rates: RateEngine::new(),
+        snapshots: StatementSnapshots::new(),
+        progress: ProgressTracker::new(),
+        xid_tracker: XidRateTracker::new(),
+        issue_history: IssueHistory::new(),
+    };
+
+    let sampler_task = metrics_sampler.clone();
+    let sampler_manager = connection_manager.clone();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(connection_manager)
+        .manage(metrics_sampler)
+        .manage(server_data.rates.clone())
+        .manage(server_data.snapshots.clone())
+        .manage(server_data.progress.clone())
+        .manage(server_data.xid_tracker.clone())
+        .manage(RuleRegistry::new())
+        .manage(server_data.issue_history.clone())
+        .manage(server_data)
+        .setup(move |_app| {
+            tauri::async_runtime::spawn(sampler_task.run(sampler_manager));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             test_connection,
             add_server,
@@ -18,19 +45,60 @@ pub fn run() {
             get_server_info,
             list_servers,
             reconnect_server,
+            preview_server_import,
+            import_servers,
+            discover_topology,
             get_database_stats,
             get_top_queries,
             get_active_queries,
             get_table_stats,
             get_index_stats,
+            get_table_bloat,
+            get_index_bloat,
             get_locks,
+            get_lock_tree,
+            cancel_backend,
+            terminate_backend,
+            terminate_idle_in_transaction,
+            get_audit_log,
             get_bgwriter_stats,
+            get_replication_stats,
+            get_replication_slots,
+            get_wal_receiver,
+            get_recovery_status,
+            get_maintenance_progress,
             get_database_sizes,
+            get_relation_sizes,
             get_all_settings,
+            get_typed_settings,
             get_hardware_info,
+            get_hardware_profile,
+            set_hardware_profile,
+            generate_tuned_config,
             analyze_configuration,
             detect_performance_issues,
+            list_rules,
+            set_rule_enabled,
+            get_analyzer_config_status,
+            reload_analyzer_config,
+            track_configuration_issues,
+            track_performance_issues,
+            get_issue_history,
+            acknowledge_issue,
+            clear_issue_acknowledgement,
+            get_server_health,
+            get_fleet_health,
+            get_wraparound_report,
+            get_index_report,
             explain_query,
+            suggest_indexes,
+            get_server_rates,
+            take_statements_snapshot,
+            list_statements_snapshots,
+            diff_statements_snapshots,
+            get_metric_history,
+            get_sampler_config,
+            update_sampler_config,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
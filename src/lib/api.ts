@@ -6,6 +6,7 @@ import type {
   ExplainPlan,
   DatabaseStats,
   QueryStat,
+  QuerySortKey,
   ActiveQuery,
   TableStats,
   IndexStats,
@@ -49,8 +50,12 @@ export const api = {
     return await invoke('get_database_stats', { serverId });
   },
 
-  async getTopQueries(serverId: string, limit: number = 10): Promise<QueryStat[]> {
-    return await invoke('get_top_queries', { serverId, limit });
+  async getTopQueries(
+    serverId: string,
+    limit: number = 10,
+    sortBy: QuerySortKey = 'total_time'
+  ): Promise<QueryStat[]> {
+    return await invoke('get_top_queries', { serverId, limit, sortBy });
   },
 
   async getActiveQueries(serverId: string): Promise<ActiveQuery[]> {
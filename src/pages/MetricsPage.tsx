@@ -444,7 +444,7 @@ export default function MetricsPage() {
                 <div>
                   <p className="text-xs text-[var(--text-tertiary)] mb-1">Buffers Backend</p>
                   <p className="text-xl font-semibold text-[var(--text-primary)] tabular-nums">
-                    {bgWriterStats.buffers_backend.toLocaleString()}
+                    {bgWriterStats.buffers_backend?.toLocaleString() ?? '—'}
                   </p>
                 </div>
               </div>
@@ -7,8 +7,27 @@ export interface ConnectionConfig {
   username: string;
   password: string;
   use_ssl: boolean;
+  ssl_mode?: SslMode | null;
+  ssl_root_cert?: string | null;
+  ssl_client_cert?: string | null;
+  ssl_client_key?: string | null;
+  ssh_tunnel?: SshTunnelConfig | null;
 }
 
+export type KnownHostsPolicy = 'strict' | 'accept-new' | 'ignore';
+
+export interface SshTunnelConfig {
+  host: string;
+  port: number;
+  username: string;
+  key_path?: string | null;
+  passphrase?: string;
+  known_hosts_policy?: KnownHostsPolicy;
+  known_hosts_path?: string | null;
+}
+
+export type SslMode = 'disable' | 'prefer' | 'require' | 'verify-ca' | 'verify-full';
+
 export interface ServerInfo {
   id: string;
   name: string;
@@ -53,6 +72,9 @@ export interface DatabaseStats {
 }
 
 export interface QueryStat {
+  queryid: number | null;
+  userid: number;
+  dbid: number;
   query: string;
   calls: number;
   total_exec_time: number;
@@ -62,8 +84,21 @@ export interface QueryStat {
   rows: number;
   shared_blks_hit: number;
   shared_blks_read: number;
+  local_blks_read: number;
+  temp_blks_read: number;
+  temp_blks_written: number;
+  plans: number | null;
+  total_plan_time: number | null;
+  wal_bytes: number | null;
+  jit_functions: number | null;
+  jit_generation_time: number | null;
+  jit_inlining_time: number | null;
+  jit_optimization_time: number | null;
+  jit_emission_time: number | null;
 }
 
+export type QuerySortKey = 'total_time' | 'mean_time' | 'calls' | 'io' | 'temp' | 'wal';
+
 export interface ActiveQuery {
   pid: number;
   usename: string;
@@ -118,8 +153,13 @@ export interface BgWriterStats {
   buffers_checkpoint: number;
   buffers_clean: number;
   maxwritten_clean: number;
-  buffers_backend: number;
+  buffers_backend: number | null;
   buffers_alloc: number;
+  stats_reset?: string | null;
+  restartpoints_timed?: number | null;
+  restartpoints_req?: number | null;
+  restartpoints_done?: number | null;
+  checkpointer_stats_reset?: string | null;
 }
 
 export interface DatabaseSize {
@@ -140,11 +180,16 @@ export interface PostgresConfig {
   max_val: string | null;
 }
 
+export type HardwareSource = 'profile' | 'system_stats' | 'proc_fs' | 'version' | 'estimate';
+
 export interface HardwareInfo {
   cpu_cores: number;
   total_memory_mb: number;
   postgres_version: string;
   os_type: string;
+  cpu_cores_source: HardwareSource;
+  total_memory_source: HardwareSource;
+  os_type_source: HardwareSource;
 }
 
 // Analysis Types
@@ -162,12 +207,14 @@ export interface PerformanceIssue {
   description: string;
   recommendation: string;
   details: string | null;
+  object: string | null;
 }
 
 // EXPLAIN Plan Types
 export interface ExplainNode {
   node_type: string;
   relation_name: string | null;
+  schema: string | null;
   alias: string | null;
   startup_cost: number;
   total_cost: number;